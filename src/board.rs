@@ -1,11 +1,22 @@
 pub(crate) mod board_map;
 pub mod bot_map;
-pub(crate) mod signal_map;
+pub mod signal_map;
+pub mod pose;
+pub mod overhead_controller;
+pub mod region;
+pub mod light_map;
+pub mod temperature_map;
 
 use std::fmt;
 use crate::board::bot_map::{BotMap, BotLocation};
 use crate::board::signal_map::SignalMap;
+use crate::board::overhead_controller::OverheadController;
+use crate::board::region::Region;
+use crate::board::light_map::LightMap;
+use crate::board::temperature_map::TemperatureMap;
+use crate::board::pose::Pose;
 use crate::kilobot::Kilobot;
+use crate::kilobot::transceiver::CommStats;
 use crate::board::board_map::BoardMap;
 
 pub const NORTH: u16 = 0;
@@ -13,6 +24,10 @@ pub const EAST: u16 = 90;
 pub const SOUTH: u16 = 180;
 pub const WEST: u16 = 270;
 
+/// Width and height of a single board space, in mm. Small enough that two 33mm kilobots can't
+/// share a space without overlapping, so every bot gets a space of its own
+pub const CELL_SIZE: f64 = 20.0;
+
 /// Basic error tpe that encompasses errors that can occur related to the board.
 /// Doesn't carry any sort of message
 pub enum LocationError {
@@ -54,18 +69,33 @@ impl CoordinatePair
     }
 
     /// Clone the coordinate pair - just copies the x and y values
+    #[allow(clippy::should_implement_trait)]
     pub fn clone(&self) -> CoordinatePair
     {
         CoordinatePair::new(self.x, self.y)
     }
 }
 
+/// An area of the board that charges the batteries of bots in the CHARGING state
+/// # Fields
+/// * 'region' - Area covered by the charger
+/// * 'current' - Charging current, in mA
+pub struct Charger
+{
+    pub region: Region,
+    pub current: f64,
+}
+
 pub struct Board
 {
     width: usize,
     height: usize,
     pub bot_map: BotMap,
     pub signal_map: SignalMap,
+    pub overhead_controller: OverheadController,
+    pub light_map: LightMap,
+    pub temperature_map: TemperatureMap,
+    chargers: Vec<Charger>,
 }
 
 impl Board
@@ -82,13 +112,21 @@ impl Board
     ///         where '*' represents "None"
     pub fn new(width: usize, height: usize) -> Board
     {
-        Board{width, height, bot_map: BotMap::new(width, height), signal_map: SignalMap::new(width, height) }
+        Board{width, height, bot_map: BotMap::new(width, height), signal_map: SignalMap::new(width, height),
+            overhead_controller: OverheadController::new(),
+            light_map: LightMap::new(), temperature_map: TemperatureMap::new(), chargers: Vec::new() }
     }
 
     /// Returns the length of the Vector representing the board
     pub fn len(&self) -> usize
     {
-        (self.width * self.height) as usize
+        self.width * self.height
+    }
+
+    /// Returns whether the board has no spaces at all
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
     }
 
     /// Add new bot to the board at the given index
@@ -142,7 +180,7 @@ impl Board
     /// * 'index' - Index to get BotLocation from
     /// # Returns
     /// * Ok - Reference to BotLocation at given index
-    /// LocationError if None or Out of Bounds
+    ///   LocationError if None or Out of Bounds
     pub fn get_bot_location_at_index(&self, index: usize) -> Result<&BotLocation, LocationError>
     {
         self.bot_map.get_bot_location_at_index(index)
@@ -159,6 +197,56 @@ impl Board
         self.bot_map.index_is_occupied(index)
     }
 
+    /// Add a charger to the board
+    /// # Arguments
+    /// * 'region' - Area covered by the charger
+    /// * 'current' - Charging current, in mA
+    pub fn add_charger(&mut self, region: Region, current: f64)
+    {
+        self.chargers.push(Charger { region, current });
+    }
+
+    /// Returns every charger on the board
+    pub fn get_chargers(&self) -> &Vec<Charger>
+    {
+        &self.chargers
+    }
+
+    /// Gets the charging current available to a bot. Overlapping chargers don't add up
+    /// # Arguments
+    /// * 'pose' - Pose of the bot
+    /// # Returns
+    /// * Current of the strongest charger the bot is on in mA, or 0 if it isn't on a charger
+    pub fn get_charger_current_at(&self, pose: &Pose) -> f64
+    {
+        self.chargers.iter()
+            .filter(|charger| charger.region.contains(pose))
+            .map(|charger| charger.current)
+            .fold(0.0, f64::max)
+    }
+
+    /// Get the communication stats of every bot on the board
+    /// # Returns
+    /// * (uid, stats) of each bot, in the order of their indices on the board
+    pub fn get_comm_report(&self) -> Vec<(u16, CommStats)>
+    {
+        self.bot_map.get_occupied_indices().iter()
+            .filter_map(|&index| self.get_bot_at_index(index).ok())
+            .map(|bot| (bot.get_uid(), *bot.get_transceiver().get_stats()))
+            .collect()
+    }
+
+    /// Get the communication stats of every bot on the board added together
+    pub fn get_comm_stats(&self) -> CommStats
+    {
+        let mut total = CommStats::default();
+        for (_uid, stats) in self.get_comm_report()
+        {
+            total.add(&stats);
+        }
+        total
+    }
+
     //pub fn get_signals_at_index(&self, index: usize) -> Result<Vec<Signal>>
 
 
@@ -1,4 +1,4 @@
-use crate::board::{CoordinatePair, LocationError};
+use crate::board::{CoordinatePair, LocationError, CELL_SIZE};
 
 pub trait BoardMap
 {
@@ -12,6 +12,18 @@ pub trait BoardMap
     /// * Height of the board
     fn get_height(&self) -> usize;
 
+    /// Get the width of the board in mm
+    fn get_width_mm(&self) -> f64
+    {
+        self.get_width() as f64 * CELL_SIZE
+    }
+
+    /// Get the height of the board in mm
+    fn get_height_mm(&self) -> f64
+    {
+        self.get_height() as f64 * CELL_SIZE
+    }
+
     /// Get the array index from an x and y coordinate
     /// # Arguments
     /// * 'x' - X coordinate
@@ -54,4 +66,24 @@ pub trait BoardMap
     {
         self.get_width() * self.get_height()
     }
-}
\ No newline at end of file
+
+    /// Gets the coordinates of the space containing a position on the board
+    /// # Arguments
+    /// * 'x' - Distance east of the left edge of the board, in mm
+    /// * 'y' - Distance south of the top edge of the board, in mm
+    /// # Returns
+    /// * Ok - Coordinate pair of the space containing the position
+    /// * LocationError if the position is off the board
+    fn get_coord_from_position(&self, x: f64, y: f64) -> Result<CoordinatePair, LocationError>
+    {
+        if x >= 0.0 && y >= 0.0
+        {
+            let coord = CoordinatePair{ x: (x / CELL_SIZE) as usize, y: (y / CELL_SIZE) as usize };
+            if coord.x < self.get_width() && coord.y < self.get_height()
+            {
+                return Ok(coord);
+            }
+        }
+        Err(LocationError::OutOfBounds)
+    }
+}
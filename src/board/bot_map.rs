@@ -1,14 +1,15 @@
 use crate::kilobot::Kilobot;
 use crate::board::{CoordinatePair, LocationError};
-use std::{mem, fmt};
+use std::fmt;
 use crate::board::board_map::BoardMap;
+use crate::board::pose::Pose;
 
 /// Struct representing the field that Kilobots move on
 /// # Fields
 /// * 'width' - Width of the board
 /// * 'height' - Height of the board
 /// * 'locations' - Packed vector of Option<BotLocation> representing each space on the board, where
-///  any index that is not null has a bot, and any index that is null has no bot
+///   any index that is not null has a bot, and any index that is null has no bot
 pub struct BotMap
 {
     width: usize,
@@ -31,7 +32,7 @@ impl BotMap
     ///         where '*' represents "None"
     pub fn new(width: usize, height: usize) -> BotMap
     {
-        let mut new_map = BotMap{width, height, bots: Vec::with_capacity((width * height).into())};
+        let mut new_map = BotMap{width, height, bots: Vec::with_capacity(width * height)};
         for _i in 0..width * height
         {
             new_map.bots.push(None);
@@ -49,33 +50,42 @@ impl BotMap
     /// LocationError if out of bounds or coordinates already occupied
     pub fn add_new_bot_at_index(&mut self, bot: Kilobot, index: usize, facing: u16) -> Option<LocationError>
     {
-        if index >= self.len()
+        match self.get_coord_from_index(&index)
         {
-            Some(LocationError::OutOfBounds);
-        }
-        match self.bots[index] {
-            Some(_) => Some(LocationError::AlreadyOccupied),
-            None => {
-                mem::swap(&mut self.bots[index], &mut Some(BotLocation { bot, facing }));
-                None
-            }
+            Ok(coord) => {
+                match self.bots[index] {
+                    Some(_) => Some(LocationError::AlreadyOccupied),
+                    None => {
+                        self.bots[index] = Some(BotLocation::new(bot, Pose::at_coord(&coord, facing as f64)));
+                        None
+                    }
+                }
+            },
+            Err(e) => Some(e),
         }
     }
 
-    /// Adds an existing BotLocation to the given index
+    /// Adds an existing BotLocation to the given index. If the pose of the BotLocation isn't inside
+    /// the space at that index, the bot is moved to the center of the space
     /// # Arguments
     /// * 'bot_loc' - Existing BotLocation object
     /// * 'index' - Index to insert into
     /// # Returns
     /// Option<LocationError> if coordinates are out of bounds, or there is already a bot at the coordinates
-    pub fn add_bot_location_at_index(&mut self, bot_loc: BotLocation, index: usize) -> Option<LocationError>
+    pub fn add_bot_location_at_index(&mut self, mut bot_loc: BotLocation, index: usize) -> Option<LocationError>
     {
         if index < self.bots.len()
         {
             match self.bots.get(index).unwrap().as_ref() {
                 Some(_) => Some(LocationError::AlreadyOccupied),
                 None => {
-                    mem::swap(&mut self.bots[index], &mut Some(bot_loc));
+                    if self.get_index_from_position(bot_loc.pose.x, bot_loc.pose.y).ok() != Some(index)
+                    {
+                        let coord = self.get_coord_from_index(&index).ok().unwrap();
+                        bot_loc.pose = Pose::at_coord(&coord, bot_loc.pose.get_heading());
+                    }
+                    bot_loc.settle();
+                    self.bots[index] = Some(bot_loc);
                     None
                 }
             }
@@ -94,12 +104,9 @@ impl BotMap
     {
         if index < self.bots.len()
         {
-            match self.bots.get(index)
+            match self.bots[index].take()
             {
-                Some(_b) => {
-                    let bot = mem::replace(&mut self.bots[index], None);
-                    Ok(Box::new(bot.unwrap()))
-                },
+                Some(bot) => Ok(Box::new(bot)),
                 None => Err(LocationError::NotOccupied),
             }
 
@@ -123,7 +130,7 @@ impl BotMap
     /// * 'index' - Index to get BotLocation from
     /// # Returns
     /// * Ok - Reference to BotLocation at given index
-    /// LocationError if None or Out of Bounds
+    ///   LocationError if None or Out of Bounds
     pub fn get_bot_location_at_index(&self, index: usize) -> Result<&BotLocation, LocationError>
     {
         if index < self.bots.len()
@@ -138,6 +145,153 @@ impl BotMap
         else { Err(LocationError::OutOfBounds) }
     }
 
+    /// Gets a mutable reference to the BotLocation at the given index
+    /// # Arguments
+    /// * 'index' - Index to get BotLocation from
+    /// # Returns
+    /// * Ok - Mutable reference to BotLocation at given index
+    ///   LocationError if None or Out of Bounds
+    pub fn get_mut_bot_location_at_index(&mut self, index: usize) -> Result<&mut BotLocation, LocationError>
+    {
+        match self.bots.get_mut(index)
+        {
+            Some(Some(loc)) => Ok(loc),
+            Some(None) => Err(LocationError::NotOccupied),
+            None => Err(LocationError::OutOfBounds),
+        }
+    }
+
+    /// Gets the indices of every space that currently holds a bot, in ascending order
+    /// # Returns
+    /// * Vector of occupied indices
+    pub fn get_occupied_indices(&self) -> Vec<usize>
+    {
+        let mut indices = Vec::new();
+        for index in 0..self.bots.len()
+        {
+            if self.bots[index].is_some()
+            {
+                indices.push(index);
+            }
+        }
+        indices
+    }
+
+    /// Gets the indices of every occupied space within a square around an index, including the index itself
+    /// # Arguments
+    /// * 'index' - Index at the center of the square
+    /// * 'range' - Number of spaces the square extends from the center in each direction
+    /// # Returns
+    /// * Vector of occupied indices in ascending order. Empty if the index is out of bounds
+    pub fn get_occupied_indices_near(&self, index: usize, range: usize) -> Vec<usize>
+    {
+        let mut indices = Vec::new();
+        if let Ok(center) = self.get_coord_from_index(&index)
+        {
+            let bottom = (center.y + range).min(self.height - 1);
+            let right = (center.x + range).min(self.width - 1);
+            for y in center.y.saturating_sub(range)..=bottom
+            {
+                for x in center.x.saturating_sub(range)..=right
+                {
+                    let i = x + y * self.width;
+                    if self.bots[i].is_some()
+                    {
+                        indices.push(i);
+                    }
+                }
+            }
+        }
+        indices
+    }
+
+    /// Gets the free index closest to the given index
+    /// # Arguments
+    /// * 'index' - Index to search around
+    /// # Returns
+    /// * The closest unoccupied index, which is the index itself if it is free, or None if every space is taken
+    pub fn get_nearest_free_index(&self, index: usize) -> Option<usize>
+    {
+        let center = self.get_coord_from_index(&index).ok()?;
+        let mut nearest: Option<(usize, usize)> = None;
+        for i in 0..self.bots.len()
+        {
+            if self.bots[i].is_none()
+            {
+                let coord = self.get_coord_from_index(&i).ok()?;
+                let dist = coord.x.abs_diff(center.x).pow(2) + coord.y.abs_diff(center.y).pow(2);
+                if nearest.map_or(true, |(_, d)| dist < d)
+                {
+                    nearest = Some((i, dist));
+                }
+            }
+        }
+        nearest.map(|(i, _)| i)
+    }
+
+    /// Moves every bot whose pose has left its space into the space that now contains its pose.
+    /// A bot that can't be placed in its new space, because another bot is staying in it or got there
+    /// first, stays in its old space with its pose reverted to where it was at the last update. Its old
+    /// space is kept free for it until every move has been settled, so no bot is ever lost
+    pub fn update_bot_spaces(&mut self)
+    {
+        let mut moving = Vec::new();
+        for index in self.get_occupied_indices()
+        {
+            let pose = self.bots[index].as_ref().unwrap().get_pose();
+            let dest = self.get_index_from_position(pose.x, pose.y).unwrap_or(index);
+            if dest != index
+            {
+                moving.push((index, dest, self.bots[index].take().unwrap()));
+            }
+        }
+
+        //Reverting a blocked bot takes back its old space, which can block another bot, so repeat until none are
+        loop
+        {
+            let mut claimed = Vec::new();
+            let mut still_moving = Vec::new();
+            let mut reverted = false;
+            for (index, dest, mut loc) in moving
+            {
+                if self.bots[dest].is_some() || claimed.contains(&dest)
+                {
+                    loc.revert_pose();
+                    self.bots[index] = Some(loc);
+                    reverted = true;
+                } else {
+                    claimed.push(dest);
+                    still_moving.push((index, dest, loc));
+                }
+            }
+            moving = still_moving;
+            if !reverted
+            {
+                break;
+            }
+        }
+        for (_index, dest, loc) in moving
+        {
+            self.bots[dest] = Some(loc);
+        }
+        for loc in self.bots.iter_mut().flatten()
+        {
+            loc.settle();
+        }
+    }
+
+    /// Gets the index of the space containing a position on the board
+    /// # Arguments
+    /// * 'x' - Distance east of the left edge of the board, in mm
+    /// * 'y' - Distance south of the top edge of the board, in mm
+    /// # Returns
+    /// * Ok - Index of the space containing the position
+    /// * LocationError if the position is off the board
+    pub fn get_index_from_position(&self, x: f64, y: f64) -> Result<usize, LocationError>
+    {
+        self.get_index_from_coord(&self.get_coord_from_position(x, y)?)
+    }
+
     /// Returns whether the given index is occupied by a kilobot
     /// # Arguments
     /// * 'index' - Vector index to check
@@ -198,15 +352,27 @@ impl BoardMap for BotMap
 /// Struct representing a space that a Kilobot occupies
 /// # Fields
 /// * 'bot' - Kilobot at this location
-/// * 'facing'
+/// * 'pose' - Continuous position and heading of the bot. The space the bot occupies is derived from it
+/// * 'settled_pose' - Pose of the bot when it was last placed in its space on the BotMap
 pub struct BotLocation
 {
     bot: Kilobot,
-    facing: u16,            //Represents the current angle of the bot, where 0 is north
+    pose: Pose,
+    settled_pose: Pose,
 }
 
 impl BotLocation
 {
+    /// Create a new BotLocation
+    /// # Arguments
+    /// * 'bot' - Kilobot at this location
+    /// * 'pose' - Position and heading of the bot
+    pub fn new(bot: Kilobot, pose: Pose) -> BotLocation
+    {
+        let settled_pose = pose;
+        BotLocation { bot, pose, settled_pose }
+    }
+
     /// Return an immutable reference to the bot in the location
     /// Allows accessing the bot functions, but cannot change bot values
     /// # Returns
@@ -227,24 +393,43 @@ impl BotLocation
 
     /// Return the facing of the bot in the location
     /// # Returns
-    /// * The rotation of the bot in degrees clockwise away from north
+    /// * The rotation of the bot in degrees clockwise away from north, rounded to the nearest degree
     pub fn get_facing(&self) -> u16
     {
-        self.facing
+        self.pose.get_heading().round() as u16 % 360
     }
 
     /// Sets the facing of the bot in the location in degrees clockwise from north
     /// # Arguments
     /// * 'new_facing' - The new facing of the bot, in degrees clockwise from north
-    pub fn set_facing(&mut self, mut new_facing: i16)
+    pub fn set_facing(&mut self, new_facing: i16)
     {
-        new_facing = new_facing % 360;
-        if new_facing < 0
-        {
-            self.facing = (new_facing + 360) as u16
-        } else {
-            self.facing = new_facing as u16
-        }
+        self.pose.set_heading(new_facing as f64);
+    }
+
+    /// Return the continuous position and heading of the bot
+    pub fn get_pose(&self) -> &Pose
+    {
+        &self.pose
+    }
+
+    /// Return a mutable reference to the continuous position and heading of the bot.
+    /// !! Moving the bot out of its space here doesn't move it on the BotMap !!
+    pub fn get_pose_mut(&mut self) -> &mut Pose
+    {
+        &mut self.pose
+    }
+
+    /// Record the current pose as the one the bot was placed in its space with
+    fn settle(&mut self)
+    {
+        self.settled_pose = self.pose;
+    }
+
+    /// Move the bot back to the pose it was placed in its space with
+    fn revert_pose(&mut self)
+    {
+        self.pose = self.settled_pose;
     }
 }
 
@@ -271,8 +456,8 @@ impl fmt::Display for BotLocation
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
     {
-        write!(f, "[Bot: {}, Facing: {}]"
+        write!(f, "[Bot: {}, Pose: {}]"
                , self.bot
-               , self.facing)
+               , self.pose)
     }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+/*
+ * light_map
+ * Purpose: Light intensity over the board, read by the kilobots' ambient light sensors
+ *
+ * Light comes from a uniform background plus any number of point lamps hanging above the board.
+ * A lamp is brightest directly underneath it and falls off with the square of the distance from it,
+ * so the height it hangs at sets how wide its pool of light is.
+ * Intensities are in the same units as kilolib's get_ambientlight(), a 10-bit reading
+ *
+ */
+
+use crate::board::pose::Pose;
+use crate::simulation::random::Random;
+
+/// Largest reading the ambient light sensor can give
+pub const MAX_LIGHT: i16 = 1023;
+/// Default standard deviation of the noise on each ambient light reading
+pub const DEFAULT_LIGHT_NOISE: f64 = 5.0;
+
+/// A point light hanging above the board
+/// # Fields
+/// * 'x' - Distance east of the left edge of the board, in mm
+/// * 'y' - Distance south of the top edge of the board, in mm
+/// * 'intensity' - Reading directly under the lamp
+/// * 'height' - Height of the lamp above the board, in mm
+pub struct Lamp
+{
+    pub x: f64,
+    pub y: f64,
+    pub intensity: f64,
+    pub height: f64,
+}
+
+impl Lamp
+{
+    /// Get the light from this lamp at a point on the board
+    /// # Arguments
+    /// * 'pose' - Point on the board
+    pub fn get_intensity_at(&self, pose: &Pose) -> f64
+    {
+        let height_squared = self.height * self.height;
+        let dist_squared = (pose.x - self.x).powi(2) + (pose.y - self.y).powi(2);
+        if height_squared + dist_squared <= 0.0
+        {
+            return self.intensity;
+        }
+        self.intensity * height_squared / (height_squared + dist_squared)
+    }
+}
+
+/// The light layer over the board
+/// # Fields
+/// * 'background' - Light everywhere on the board, e.g. from the room
+/// * 'lamps' - Point lights above the board
+/// * 'noise' - Standard deviation of the noise on each ambient light reading
+pub struct LightMap
+{
+    background: f64,
+    lamps: Vec<Lamp>,
+    noise: f64,
+}
+
+impl LightMap
+{
+    /// Create a dark light map, with no background light and no lamps
+    pub fn new() -> LightMap
+    {
+        LightMap { background: 0.0, lamps: Vec::new(), noise: DEFAULT_LIGHT_NOISE }
+    }
+
+    /// Set the light everywhere on the board. Defaults to 0
+    /// # Arguments
+    /// * 'background' - Reading with no lamps on
+    pub fn set_background(&mut self, background: f64)
+    {
+        self.background = background;
+    }
+
+    /// Add a lamp above the board
+    /// # Arguments
+    /// * 'x' - Distance east of the left edge of the board, in mm
+    /// * 'y' - Distance south of the top edge of the board, in mm
+    /// * 'intensity' - Reading directly under the lamp
+    /// * 'height' - Height of the lamp above the board, in mm
+    pub fn add_lamp(&mut self, x: f64, y: f64, intensity: f64, height: f64)
+    {
+        self.lamps.push(Lamp { x, y, intensity, height });
+    }
+
+    /// Returns every lamp above the board
+    pub fn get_lamps(&self) -> &Vec<Lamp>
+    {
+        &self.lamps
+    }
+
+    /// Set how noisy the ambient light readings are. Defaults to DEFAULT_LIGHT_NOISE
+    /// # Arguments
+    /// * 'noise' - Standard deviation of the noise on each reading. 0 for no noise
+    pub fn set_noise(&mut self, noise: f64)
+    {
+        self.noise = noise.max(0.0);
+    }
+
+    /// Get the standard deviation of the noise on each ambient light reading
+    pub fn get_noise(&self) -> f64
+    {
+        self.noise
+    }
+
+    /// Get the light at a point on the board, without noise
+    /// # Arguments
+    /// * 'pose' - Point on the board
+    pub fn get_intensity_at(&self, pose: &Pose) -> f64
+    {
+        self.background + self.lamps.iter().map(|lamp| lamp.get_intensity_at(pose)).sum::<f64>()
+    }
+
+    /// Take an ambient light reading at a point on the board
+    /// # Arguments
+    /// * 'pose' - Point on the board
+    /// * 'rng' - Source of randomness for the noise
+    /// # Returns
+    /// * Noisy reading, limited to what the sensor can read
+    pub fn read(&self, pose: &Pose, rng: &mut Random) -> i16
+    {
+        let reading = self.get_intensity_at(pose) + rng.next_gaussian() * self.noise;
+        reading.round().clamp(0.0, MAX_LIGHT as f64) as i16
+    }
+}
+
+impl Default for LightMap
+{
+    fn default() -> Self
+    {
+        LightMap::new()
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * overhead_controller
+ * Purpose: Simulate the overhead IR controller (OHC) that sends commands to every bot on the board
+ *
+ * On a real setup the OHC hangs above the arena and is how experiments are started and stopped.
+ * Messages can be sent straight away, or scripted ahead of time on a timeline, e.g.
+ *      t=0 WAKEUP, t=2s RUN, t=600s SLEEP
+ * The simulation delivers them at the start of the tick they are due, before any program runs
+ *
+ */
+
+use crate::board::region::Region;
+use crate::kilobot::messages::Message;
+use crate::simulation::TICKS_PER_SECOND;
+
+/// A message the overhead controller sends at a set time
+/// # Fields
+/// * 'ticks' - Simulation tick the message is sent on
+/// * 'msg' - The message
+/// * 'region' - Area of the board the message reaches
+pub struct Broadcast
+{
+    pub ticks: u32,
+    pub msg: Message,
+    pub region: Region,
+}
+
+/// The overhead controller
+/// # Fields
+/// * 'loss_rate' - Chance of each bot missing any given message, from 0 to 1
+/// * 'timeline' - Messages waiting to be sent, in the order they will be sent
+pub struct OverheadController
+{
+    loss_rate: f64,
+    timeline: Vec<Broadcast>,
+}
+
+impl OverheadController
+{
+    /// Create an overhead controller with nothing scheduled and no message loss
+    pub fn new() -> OverheadController
+    {
+        OverheadController { loss_rate: 0.0, timeline: Vec::new() }
+    }
+
+    /// Set the chance of each bot missing any given message. Defaults to 0
+    /// # Arguments
+    /// * 'loss_rate' - Probability from 0 (every bot hears every message) to 1 (nothing gets through)
+    pub fn set_loss_rate(&mut self, loss_rate: f64)
+    {
+        self.loss_rate = loss_rate.clamp(0.0, 1.0);
+    }
+
+    /// Get the chance of each bot missing any given message
+    pub fn get_loss_rate(&self) -> f64
+    {
+        self.loss_rate
+    }
+
+    /// Send a message to every bot on the board. It is delivered at the start of the next tick
+    /// # Arguments
+    /// * 'msg' - Message to send
+    pub fn broadcast(&mut self, msg: Message)
+    {
+        self.send_to_region(msg, Region::All);
+    }
+
+    /// Send a message to the bots in part of the board. It is delivered at the start of the next tick
+    /// # Arguments
+    /// * 'msg' - Message to send
+    /// * 'region' - Area of the board the message reaches
+    pub fn send_to_region(&mut self, msg: Message, region: Region)
+    {
+        self.schedule_at_ticks(0, msg, region);
+    }
+
+    /// Schedule a message to be sent to every bot on the board
+    /// # Arguments
+    /// * 'seconds' - Time since the start of the simulation to send the message at.
+    ///   Rounded to the nearest tick
+    /// * 'msg' - Message to send
+    pub fn schedule(&mut self, seconds: f64, msg: Message)
+    {
+        self.schedule_in_region(seconds, msg, Region::All);
+    }
+
+    /// Schedule a message to be sent to the bots in part of the board
+    /// # Arguments
+    /// * 'seconds' - Time since the start of the simulation to send the message at.
+    ///   Rounded to the nearest tick
+    /// * 'msg' - Message to send
+    /// * 'region' - Area of the board the message reaches
+    pub fn schedule_in_region(&mut self, seconds: f64, msg: Message, region: Region)
+    {
+        let ticks = (seconds.max(0.0) * TICKS_PER_SECOND as f64).round() as u32;
+        self.schedule_at_ticks(ticks, msg, region);
+    }
+
+    /// Schedule a message to be sent on a given tick. Messages scheduled for the same tick are sent
+    /// in the order they were scheduled, and messages scheduled in the past are sent on the next tick
+    /// # Arguments
+    /// * 'ticks' - Simulation tick to send the message on
+    /// * 'msg' - Message to send
+    /// * 'region' - Area of the board the message reaches
+    pub fn schedule_at_ticks(&mut self, ticks: u32, msg: Message, region: Region)
+    {
+        let position = self.timeline.iter().position(|b| b.ticks > ticks).unwrap_or(self.timeline.len());
+        self.timeline.insert(position, Broadcast { ticks, msg, region });
+    }
+
+    /// Get the messages that are still waiting to be sent
+    pub fn get_timeline(&self) -> &Vec<Broadcast>
+    {
+        &self.timeline
+    }
+
+    /// Remove every message that is due to be sent, from the front of the timeline
+    /// # Arguments
+    /// * 'ticks' - Current simulation tick
+    /// # Returns
+    /// * The messages due on or before the given tick, in the order they should be sent
+    pub(crate) fn take_due(&mut self, ticks: u32) -> Vec<Broadcast>
+    {
+        let count = self.timeline.iter().take_while(|b| b.ticks <= ticks).count();
+        self.timeline.drain(..count).collect()
+    }
+}
+
+impl Default for OverheadController
+{
+    fn default() -> Self
+    {
+        OverheadController::new()
+    }
+}
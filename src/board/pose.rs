@@ -0,0 +1,105 @@
+/*
+ * pose
+ * Purpose: Continuous position and heading of a bot on the board
+ *
+ * Positions are in millimetres from the top left corner of the board, with x increasing to the
+ * east and y increasing to the south, same as board coordinates. Heading is in degrees clockwise
+ * from north, same as the facing of a BotLocation
+ *
+ */
+
+use std::fmt;
+use crate::board::{CoordinatePair, CELL_SIZE};
+
+/// Position and heading of a bot in continuous space
+/// # Fields
+/// * 'x' - Distance east of the left edge of the board, in mm
+/// * 'y' - Distance south of the top edge of the board, in mm
+/// * 'heading' - Direction the bot is facing in degrees clockwise from north. Always in [0, 360)
+#[derive(Clone, Copy)]
+pub struct Pose
+{
+    pub x: f64,
+    pub y: f64,
+    heading: f64,
+}
+
+impl Pose
+{
+    /// Create a new Pose
+    /// # Arguments
+    /// * 'x' - Distance east of the left edge of the board, in mm
+    /// * 'y' - Distance south of the top edge of the board, in mm
+    /// * 'heading' - Direction the bot is facing in degrees clockwise from north
+    pub fn new(x: f64, y: f64, heading: f64) -> Pose
+    {
+        let mut pose = Pose { x, y, heading: 0.0 };
+        pose.set_heading(heading);
+        pose
+    }
+
+    /// Create a Pose in the center of a board space
+    /// # Arguments
+    /// * 'coord' - Board space to place the pose in
+    /// * 'heading' - Direction the bot is facing in degrees clockwise from north
+    pub fn at_coord(coord: &CoordinatePair, heading: f64) -> Pose
+    {
+        let (x, y) = coord.as_f64_tuple();
+        Pose::new((x + 0.5) * CELL_SIZE, (y + 0.5) * CELL_SIZE, heading)
+    }
+
+    /// Get the heading of the pose
+    /// # Returns
+    /// * Heading in degrees clockwise from north, in [0, 360)
+    pub fn get_heading(&self) -> f64
+    {
+        self.heading
+    }
+
+    /// Set the heading of the pose, wrapping it into [0, 360)
+    /// # Arguments
+    /// * 'heading' - New heading in degrees clockwise from north
+    pub fn set_heading(&mut self, heading: f64)
+    {
+        self.heading = heading.rem_euclid(360.0);
+        //rem_euclid can round up to exactly 360 for tiny negative values
+        if self.heading >= 360.0
+        {
+            self.heading = 0.0;
+        }
+    }
+
+    /// Get the distance between this pose and another one
+    /// # Arguments
+    /// * 'other' - Pose to measure the distance to
+    /// # Returns
+    /// * Distance between the two positions in mm
+    pub fn distance_to(&self, other: &Pose) -> f64
+    {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
+    /// Move the pose along an arc. The heading changes at a constant rate over the arc,
+    /// so the position is advanced along the heading halfway through the turn
+    /// # Arguments
+    /// * 'distance' - Distance travelled forward along the arc, in mm
+    /// * 'rotation' - Degrees turned over the arc, clockwise if positive
+    pub fn advance(&mut self, distance: f64, rotation: f64)
+    {
+        let mid_heading = (self.heading + rotation / 2.0).to_radians();
+        self.x += distance * mid_heading.sin();
+        self.y -= distance * mid_heading.cos();
+        self.set_heading(self.heading + rotation);
+    }
+}
+
+impl fmt::Display for Pose
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "(x:{:.1}mm, y:{:.1}mm, heading:{:.1})"
+               , self.x
+               , self.y
+               , self.heading)
+    }
+}
@@ -0,0 +1,36 @@
+/*
+ * region
+ * Purpose: Describe areas of the board in continuous space
+ *
+ */
+
+use crate::board::pose::Pose;
+
+/// An area of the board, e.g. the part a message from the overhead controller reaches, or a charger.
+/// Positions are in mm, same as a Pose
+pub enum Region
+{
+    /// The whole board
+    All,
+    /// A rectangle, given by its top left corner and its size
+    Rect { x: f64, y: f64, width: f64, height: f64 },
+    /// A circle, given by its center and radius
+    Circle { x: f64, y: f64, radius: f64 },
+}
+
+impl Region
+{
+    /// Determines whether a bot is inside the region. Only the center of the bot counts
+    /// # Arguments
+    /// * 'pose' - Pose of the bot
+    pub fn contains(&self, pose: &Pose) -> bool
+    {
+        match *self
+        {
+            Region::All => true,
+            Region::Rect { x, y, width, height } =>
+                pose.x >= x && pose.x < x + width && pose.y >= y && pose.y < y + height,
+            Region::Circle { x, y, radius } => (pose.x - x).hypot(pose.y - y) <= radius,
+        }
+    }
+}
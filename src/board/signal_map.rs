@@ -7,8 +7,8 @@
 
 use crate::board::{CoordinatePair, LocationError};
 use crate::board::board_map::BoardMap;
-use std::{mem, fmt};
-use std::cmp::{max, min};
+use std::fmt;
+use std::cmp::min;
 
 /// Map of all broadcasts and their range on the board
 pub struct SignalMap
@@ -73,7 +73,7 @@ impl SignalMap
                     Some(_) => Some(LocationError::AlreadyOccupied),
                     None => {
                         self.fill_circle(&source, Signal::add_source);
-                        mem::swap(&mut self.sources[index], &mut Some(source));
+                        self.sources[index] = Some(source);
                         None
                     },
                 }
@@ -95,7 +95,7 @@ impl SignalMap
                 match self.sources.get(index)
                 {
                     Some(_s) => {
-                        let mut src = mem::replace(&mut self.sources[index], None).unwrap();
+                        let src = self.sources[index].take().unwrap();
                         self.fill_circle(&src, Signal::remove_source);
                         Ok(src)
                     },
@@ -155,10 +155,10 @@ impl SignalMap
     /// * None if the move was successful, LocationError if any snags were hit
     pub fn move_source_to_coord(&mut self, src: &CoordinatePair, dest: &CoordinatePair) -> Option<LocationError>
     {
-        match self.get_index_from_coord(&src)
+        match self.get_index_from_coord(src)
         {
             Ok(_) => {
-                match self.get_index_from_coord(&dest)
+                match self.get_index_from_coord(dest)
                 {
                     Ok(dest_index) => {
                         match self.sources.get_mut(dest_index).unwrap()
@@ -228,18 +228,19 @@ impl SignalMap
         distance_squared <= radius*radius
     }
 
-    /// Helper function to get the bounding box of a circle. !!Does not check if center is out of bounds!!
+    /// Helper function to get the bounding box of a circle, clipped to the edges of the map.
+    /// !!Does not check if center is out of bounds!!
     /// # Arguments
     /// * 'center' - The coordinates of the center of the circle
     /// * 'radius' - the radius of the circle
     /// # Returns
-    /// (top, bottom, left, right) - The boundaries of the circle as tuple
+    /// (top, bottom, left, right) - The boundaries of the circle as tuple. Bottom and right are exclusive
     fn get_bounding_box(&self, center: &CoordinatePair, radius: f64) -> (usize,usize,usize,usize)
     {
-        let top: usize = min(0, (center.y as f64 - radius) as usize);
-        let bottom: usize = max(self.height, (center.y as f64 + radius) as usize);
-        let left: usize = min(0, (center.x as f64 - radius) as usize);
-        let right: usize = max(self.width, (center.x as f64 + radius) as usize);
+        let top: usize = (center.y as f64 - radius).max(0.0) as usize;
+        let bottom: usize = min(self.height, (center.y as f64 + radius) as usize + 1);
+        let left: usize = (center.x as f64 - radius).max(0.0) as usize;
+        let right: usize = min(self.width, (center.x as f64 + radius) as usize + 1);
 
         (top, bottom, left, right)
     }
@@ -279,7 +280,7 @@ impl SignalMap
             if self.sources[index].is_some()
             {
                 txt = "O".parse().unwrap();
-            } else if self.signals[index].sources.len() > 0
+            } else if !self.signals[index].sources.is_empty()
             {
                 txt = "#".parse().unwrap();
             } else {
@@ -353,6 +354,7 @@ impl Signal
     }
 
     /// Clone a source Signal object
+    #[allow(clippy::should_implement_trait)]
     pub fn clone(src: &Signal) -> Signal
     {
         let mut new_sig = Signal{ sources: vec![] };
@@ -0,0 +1,158 @@
+/*
+ * temperature_map
+ * Purpose: Temperature over the board, read by the kilobots' temperature sensors
+ *
+ * The temperature is a uniform ambient temperature, plus an optional linear gradient across the board
+ * and any number of hotspots (or cold spots, with a negative rise) that fade out with distance.
+ * Temperatures are in degrees Celsius, and readings are on the same scale as kilolib's
+ * get_temperature(), which reads the ATmega328's on-chip sensor through its 10-bit ADC
+ *
+ */
+
+use crate::board::pose::Pose;
+use crate::simulation::random::Random;
+
+/// Default temperature of the room, in degrees Celsius
+pub const DEFAULT_AMBIENT_TEMPERATURE: f64 = 25.0;
+/// get_temperature() reading at 0 degrees Celsius
+pub const TEMPERATURE_READING_AT_ZERO: f64 = 269.0;
+/// Rise in the get_temperature() reading per degree Celsius
+pub const TEMPERATURE_READING_PER_DEGREE: f64 = 0.93;
+/// Largest reading the temperature sensor can give
+pub const MAX_TEMPERATURE_READING: i16 = 1023;
+/// Default standard deviation of the noise on each temperature reading
+pub const DEFAULT_TEMPERATURE_NOISE: f64 = 1.0;
+
+/// A spot on the board that is warmer (or colder) than its surroundings
+/// # Fields
+/// * 'x' - Distance east of the left edge of the board, in mm
+/// * 'y' - Distance south of the top edge of the board, in mm
+/// * 'rise' - Temperature rise at the centre of the hotspot, in degrees Celsius. Negative for a cold spot
+/// * 'radius' - Distance from the centre at which the rise has fallen to about 60%, in mm
+pub struct Hotspot
+{
+    pub x: f64,
+    pub y: f64,
+    pub rise: f64,
+    pub radius: f64,
+}
+
+impl Hotspot
+{
+    /// Get the temperature rise from this hotspot at a point on the board
+    /// # Arguments
+    /// * 'pose' - Point on the board
+    pub fn get_rise_at(&self, pose: &Pose) -> f64
+    {
+        if self.radius <= 0.0
+        {
+            return 0.0;
+        }
+        let dist_squared = (pose.x - self.x).powi(2) + (pose.y - self.y).powi(2);
+        self.rise * (-dist_squared / (2.0 * self.radius * self.radius)).exp()
+    }
+}
+
+/// The temperature layer over the board
+/// # Fields
+/// * 'ambient' - Temperature at the top left corner of the board, in degrees Celsius
+/// * 'gradient' - Rise in temperature per mm east and per mm south
+/// * 'hotspots' - Warm and cold spots on the board
+/// * 'noise' - Standard deviation of the noise on each temperature reading
+pub struct TemperatureMap
+{
+    ambient: f64,
+    gradient: (f64, f64),
+    hotspots: Vec<Hotspot>,
+    noise: f64,
+}
+
+impl TemperatureMap
+{
+    /// Create a temperature map at DEFAULT_AMBIENT_TEMPERATURE everywhere
+    pub fn new() -> TemperatureMap
+    {
+        TemperatureMap { ambient: DEFAULT_AMBIENT_TEMPERATURE, gradient: (0.0, 0.0), hotspots: Vec::new(),
+            noise: DEFAULT_TEMPERATURE_NOISE }
+    }
+
+    /// Set the temperature of the room. Defaults to DEFAULT_AMBIENT_TEMPERATURE
+    /// # Arguments
+    /// * 'ambient' - Temperature at the top left corner of the board, in degrees Celsius
+    pub fn set_ambient(&mut self, ambient: f64)
+    {
+        self.ambient = ambient;
+    }
+
+    /// Set a linear temperature gradient across the board. Defaults to none
+    /// # Arguments
+    /// * 'per_mm_east' - Rise in temperature per mm east, in degrees Celsius
+    /// * 'per_mm_south' - Rise in temperature per mm south, in degrees Celsius
+    pub fn set_gradient(&mut self, per_mm_east: f64, per_mm_south: f64)
+    {
+        self.gradient = (per_mm_east, per_mm_south);
+    }
+
+    /// Add a hotspot to the board
+    /// # Arguments
+    /// * 'x' - Distance east of the left edge of the board, in mm
+    /// * 'y' - Distance south of the top edge of the board, in mm
+    /// * 'rise' - Temperature rise at the centre, in degrees Celsius. Negative for a cold spot
+    /// * 'radius' - Size of the hotspot, in mm
+    pub fn add_hotspot(&mut self, x: f64, y: f64, rise: f64, radius: f64)
+    {
+        self.hotspots.push(Hotspot { x, y, rise, radius });
+    }
+
+    /// Returns every hotspot on the board
+    pub fn get_hotspots(&self) -> &Vec<Hotspot>
+    {
+        &self.hotspots
+    }
+
+    /// Set how noisy the temperature readings are. Defaults to DEFAULT_TEMPERATURE_NOISE
+    /// # Arguments
+    /// * 'noise' - Standard deviation of the noise on each reading. 0 for no noise
+    pub fn set_noise(&mut self, noise: f64)
+    {
+        self.noise = noise.max(0.0);
+    }
+
+    /// Get the standard deviation of the noise on each temperature reading
+    pub fn get_noise(&self) -> f64
+    {
+        self.noise
+    }
+
+    /// Get the temperature at a point on the board
+    /// # Arguments
+    /// * 'pose' - Point on the board
+    /// # Returns
+    /// * Temperature in degrees Celsius
+    pub fn get_temperature_at(&self, pose: &Pose) -> f64
+    {
+        self.ambient + self.gradient.0 * pose.x + self.gradient.1 * pose.y
+            + self.hotspots.iter().map(|spot| spot.get_rise_at(pose)).sum::<f64>()
+    }
+
+    /// Take a temperature reading at a point on the board
+    /// # Arguments
+    /// * 'pose' - Point on the board
+    /// * 'rng' - Source of randomness for the noise
+    /// # Returns
+    /// * Noisy reading, limited to what the sensor can read
+    pub fn read(&self, pose: &Pose, rng: &mut Random) -> i16
+    {
+        let reading = TEMPERATURE_READING_AT_ZERO + self.get_temperature_at(pose) * TEMPERATURE_READING_PER_DEGREE
+            + rng.next_gaussian() * self.noise;
+        reading.round().clamp(0.0, MAX_TEMPERATURE_READING as f64) as i16
+    }
+}
+
+impl Default for TemperatureMap
+{
+    fn default() -> Self
+    {
+        TemperatureMap::new()
+    }
+}
@@ -0,0 +1,162 @@
+/*
+ * hal
+ * Purpose: Hardware abstraction layer between user programs and the kilobot they run on
+ *
+ * The functions mirror kilolib, so a controller written against Hal only depends on the kilolib
+ * surface. The simulator implements Hal for every Kilobot on the board, and a backend for real
+ * hardware only needs to implement the same trait for the controller to run on it unchanged.
+ * The types passed across it, such as the transceiver callbacks and distance measurements, are
+ * defined here too, so a hardware backend doesn't need to depend on the simulator
+ *
+ */
+
+use crate::kilobot::messages::Message;
+use crate::kilobot::rgb::RGB;
+
+/// Value returned by the sensor functions when a reading couldn't be taken. Same as kilolib
+pub const SENSOR_ERROR: i16 = -1;
+
+/// Callback that is asked for a message whenever the transceiver is ready to transmit
+pub type MessageTxCallback = Box<dyn FnMut() -> Option<Message>>;
+/// Callback that is handed every message received, along with the signal strength it was received with
+pub type MessageRxCallback = Box<dyn FnMut(Message, DistanceMeasurement)>;
+/// Callback that is told when something happens, e.g. a successful transmission
+pub type EventCallback = Box<dyn FnMut()>;
+
+/// Raw signal strength of a received message. Same as kilolib's distance_measurement_t
+/// # Fields
+/// * 'low_gain' - Reading through the low gain amplifier
+/// * 'high_gain' - Reading through the high gain amplifier
+#[derive(Clone, Copy)]
+pub struct DistanceMeasurement
+{
+    pub low_gain: i16,
+    pub high_gain: i16,
+}
+
+impl DistanceMeasurement
+{
+    /// Create a new DistanceMeasurement
+    /// # Arguments
+    /// * 'low_gain' - Reading through the low gain amplifier
+    /// * 'high_gain' - Reading through the high gain amplifier
+    pub fn new(low_gain: i16, high_gain: i16) -> DistanceMeasurement
+    {
+        DistanceMeasurement { low_gain, high_gain }
+    }
+}
+
+/// Hardware of a single kilobot, as seen by a user program
+pub trait Hal
+{
+    /// Set the duty cycle of the motors. Same as kilolib's set_motors(left, right)
+    /// # Arguments
+    /// * 'left' - Duty cycle of the left motor
+    /// * 'right' - Duty cycle of the right motor
+    fn set_motors(&mut self, left: u8, right: u8);
+
+    /// Run both motors at full power for a moment, to get them going before setting a low duty cycle.
+    /// Same as kilolib's spinup_motors()
+    fn spinup_motors(&mut self);
+
+    /// Set the color of the RGB LED. Same as kilolib's set_color(color)
+    /// # Arguments
+    /// * 'color' - Color to set the LED to, e.g. RGB(1,0,0) for a dim red. The LED only has 2 bits
+    ///   per channel, so other colors are rounded to the nearest one it can show
+    fn set_color(&mut self, color: RGB);
+
+    /// Unique identifier of the bot. Same as kilolib's kilo_uid
+    fn kilo_uid(&self) -> u16;
+
+    /// Number of clock ticks since the bot started, at 32 ticks/sec. Same as kilolib's kilo_ticks.
+    /// Every bot counts on its own clock, which can be out of phase with and drift from the others.
+    /// loop_ runs once for every tick counted, so a program sees each value of kilo_ticks once
+    fn kilo_ticks(&self) -> u32;
+
+    /// Calibrated left motor value for turning left on the spot. Same as kilolib's kilo_turn_left
+    fn kilo_turn_left(&self) -> u8;
+
+    /// Calibrated right motor value for turning right on the spot. Same as kilolib's kilo_turn_right
+    fn kilo_turn_right(&self) -> u8;
+
+    /// Calibrated left motor value for driving straight. Same as kilolib's kilo_straight_left
+    fn kilo_straight_left(&self) -> u8;
+
+    /// Calibrated right motor value for driving straight. Same as kilolib's kilo_straight_right
+    fn kilo_straight_right(&self) -> u8;
+
+    /// Register the callback the IR transceiver runs when a message is received.
+    /// Same as assigning kilo_message_rx
+    /// # Arguments
+    /// * 'cb' - Function or closure taking the message and the signal strength it was received with
+    fn set_message_rx(&mut self, cb: MessageRxCallback);
+
+    /// Register the callback the IR transceiver runs after message_rx has handled a received message
+    /// # Arguments
+    /// * 'cb' - Function or closure called after each message is received
+    fn set_message_rx_success(&mut self, cb: EventCallback);
+
+    /// Register the callback the IR transceiver runs when it is ready to transmit.
+    /// Same as assigning kilo_message_tx
+    /// # Arguments
+    /// * 'cb' - Function or closure returning the message to send, or None if nothing should be sent
+    fn set_message_tx(&mut self, cb: MessageTxCallback);
+
+    /// Register the callback the IR transceiver runs after a message is transmitted.
+    /// Same as assigning kilo_message_tx_success
+    /// # Arguments
+    /// * 'cb' - Function or closure called after a successful transmission
+    fn set_message_tx_success(&mut self, cb: EventCallback);
+
+    /// Queue a message to be sent in the next free transmission slot. Queued messages are sent one per
+    /// slot, oldest first, before message_tx is asked for anything. Not part of kilolib
+    /// # Arguments
+    /// * 'msg' - Message to send
+    /// # Returns
+    /// * true if the message was queued, false if the outbox was full and the message was dropped
+    fn queue_message(&mut self, msg: Message) -> bool;
+
+    /// Set the number of clock ticks between transmissions. Same as assigning kilo_tx_period
+    /// # Arguments
+    /// * 'period' - Ticks between transmissions. Defaults to 16, twice per second
+    fn set_kilo_tx_period(&mut self, period: u32);
+
+    /// Convert the signal strength of a received message into the distance to its sender, using this
+    /// bot's calibration. Same as kilolib's estimate_distance(dist)
+    /// # Arguments
+    /// * 'dist' - Signal strength passed to the message_rx callback
+    /// # Returns
+    /// * Distance between the centers of the two bots, in mm
+    fn estimate_distance(&self, dist: &DistanceMeasurement) -> u8;
+
+    /// Get a random number from the hardware random number generator. Same as kilolib's rand_hard().
+    /// In the simulator it comes from the bot's own stream of the simulation's seed, so runs repeat
+    /// # Returns
+    /// * Random number from 0 to 255
+    fn rand_hard(&mut self) -> u8;
+
+    /// Get a random number from kilolib's software random number generator. Same as kilolib's rand_soft()
+    /// # Returns
+    /// * Random number from 0 to 255
+    fn rand_soft(&mut self) -> u8;
+
+    /// Seed the software random number generator. Same as kilolib's rand_seed(seed)
+    /// # Arguments
+    /// * 'seed' - New seed, e.g. from rand_hard()
+    fn rand_seed(&mut self, seed: u8);
+
+    /// Read the ambient light sensor. Same as kilolib's get_ambientlight()
+    /// # Returns
+    /// * 10-bit light reading, or SENSOR_ERROR if no reading could be taken
+    fn get_ambientlight(&self) -> i16;
+
+    /// Read the battery voltage. Same as kilolib's get_voltage()
+    /// # Returns
+    /// * 10-bit voltage reading, or SENSOR_ERROR if no reading could be taken
+    fn get_voltage(&self) -> i16;
+
+    /// Read the temperature sensor. Same as kilolib's get_temperature()
+    /// # Returns
+    /// * 10-bit temperature reading, or SENSOR_ERROR if no reading could be taken
+    fn get_temperature(&self) -> i16;
+}
@@ -1,12 +1,39 @@
 use std::fmt;
+use crate::kilobot::program::{KilobotProgram, KilobotApi};
+use crate::hal::SENSOR_ERROR;
+use crate::kilobot::messages::{Message, MessageType, CalibMode};
+use crate::kilobot::state::KiloState;
+use crate::kilobot::distance::{DistanceMeasurement, DistanceSensor};
+use crate::kilobot::rand::SoftRandom;
+use crate::kilobot::calibration::{Calibration, Motion};
+use crate::kilobot::motor::MotorModel;
+use crate::kilobot::clock::Clock;
+use crate::kilobot::battery::{Battery, BASE_CURRENT, SLEEP_CURRENT, MOTOR_CURRENT, LED_CURRENT};
+use crate::simulation::random::{Random, DEFAULT_SEED};
 
-mod rgb;
-mod transceiver;
-mod messages;
+pub mod rgb;
+pub mod transceiver;
+pub mod messages;
+pub mod program;
+pub mod state;
+pub mod distance;
+pub mod rand;
+pub mod calibration;
+pub mod motor;
+pub mod battery;
+pub mod clock;
 /// Max PWN frequency of the kilobot
 pub const MOTOR_MAX_VAL: u8 = 255;
 /// Speed that the bot rotates at in degrees/sec
 pub const ROTATION_SPEED: u16 = 45;
+/// Speed that the bot moves forward at in mm/sec, with both motors at max
+pub const MOVE_SPEED: f64 = 10.0;
+/// Diameter of the bot's round body in mm
+pub const BODY_DIAMETER: f64 = 33.0;
+/// Time the LED stays on for each blink while the bot is IDLE, in ms
+pub const IDLE_BLINK_ON_MS: u32 = 1;
+/// Time the LED stays off between blinks while the bot is IDLE, in ms
+pub const IDLE_BLINK_OFF_MS: u32 = 200;
 
 //Struct representing the kilobot
 /*
@@ -18,13 +45,31 @@ pub struct Kilobot
 {
     left_motor: u8,
     right_motor: u8,
+    left_spinning: bool,
+    right_spinning: bool,
+    motor_model: MotorModel,
     led: rgb::RGB,
+    led_history: Vec<rgb::LedChange>,
     uid: u16,
     message_received: bool,
-    //battery_voltage: u8,
+    transceiver: transceiver::Transceiver,
+    distance_sensor: DistanceSensor,
+    rng: Option<Random>,
+    env_rng: Option<Random>,
+    soft_rng: SoftRandom,
+    program: Option<Box<dyn KilobotProgram>>,
+    kilo_state: KiloState,
+    calibration: Calibration,
+    ideal_calibration: Calibration,
+    motion: Motion,
+    prev_motion: Motion,
+    battery: Battery,
+    charger_current: f64,
+    ambient_light: i16,
+    temperature: i16,
+    clock: Clock,
+    tx_ticks: u32,
     //sensors: sensors::Sensors,
-    //setup: fn(),
-    //loop: fn(),
 }
 // TODO: Proper documentation comments
 impl Kilobot
@@ -37,25 +82,26 @@ impl Kilobot
         Use transceiver - Should always be receiving in background
         Use LED
 
-        Turns by spinning one motor
+        Turns by spinning one motor. Same as kilolib, spinning the left motor turns left
+        and spinning the right motor turns right
     */
 
-    //Turn the kilobot left
+    //Turn the kilobot left, using its calibrated motor value
     pub fn turn_left(&mut self)
     {
-        self.set_motors(0,MOTOR_MAX_VAL);
+        self.set_motors(self.calibration.turn_left,0);
     }
 
-    //Turn the kilobot right
+    //Turn the kilobot right, using its calibrated motor value
     pub fn turn_right(&mut self)
     {
-        self.set_motors(MOTOR_MAX_VAL,0);
+        self.set_motors(0,self.calibration.turn_right);
     }
 
-    //Move straight forward
+    //Move straight forward, using its calibrated motor values
     pub fn move_forward(&mut self)
     {
-        self.set_motors(MOTOR_MAX_VAL,MOTOR_MAX_VAL);
+        self.set_motors(self.calibration.straight_left,self.calibration.straight_right);
     }
 
     //Stop moving
@@ -64,19 +110,81 @@ impl Kilobot
         self.set_motors(0,0);
     }
 
-    //Sets the motors
+    //Sets the motors. A motor at rest only starts if the duty cycle is high enough to get it going
     pub fn set_motors(&mut self, left_m: u8, right_m: u8)
     {
         self.left_motor = left_m;
         self.right_motor = right_m;
+        self.left_spinning = self.motor_model.is_spinning(self.left_spinning, left_m);
+        self.right_spinning = self.motor_model.is_spinning(self.right_spinning, right_m);
+    }
+
+    //Runs both motors at full power to get them going, same as kilolib's spinup_motors().
+    //Set the motors to the values you want straight after
+    pub fn spinup_motors(&mut self)
+    {
+        self.set_motors(MOTOR_MAX_VAL,MOTOR_MAX_VAL);
+    }
+
+    //Returns the duty cycles of the motors that are actually spinning, with 0 for a stalled motor
+    pub fn get_spinning_motor_values(&self) -> (u8, u8)
+    {
+        (if self.left_spinning { self.left_motor } else { 0 },
+         if self.right_spinning { self.right_motor } else { 0 })
+    }
+
+    /// Returns how the bot's motors respond to their duty cycle
+    pub fn get_motor_model(&self) -> &MotorModel
+    {
+        &self.motor_model
+    }
+
+    /// Set how the bot's motors respond to their duty cycle. Defaults to MotorModel::default(), which
+    /// needs spinning up. MotorModel::linear() gives motors that respond instantly and linearly
+    /// # Arguments
+    /// * 'model' - New motor model
+    pub fn set_motor_model(&mut self, model: MotorModel)
+    {
+        self.motor_model = model;
     }
 
-    //Set the color of the LED
+    //Set the color of the LED, rounded to the nearest color it can show
     pub fn set_led(&mut self, r: u8, g: u8, b: u8)
     {
         self.led.set(rgb::RGB{r,g,b});
     }
 
+    //Returns the current color of the LED
+    pub fn get_led(&self) -> &rgb::RGB
+    {
+        self.led.get()
+    }
+
+    /// Returns every change of the LED's color since the bot started, oldest first. The LED starts off,
+    /// and a change is recorded at the end of any tick the bot finishes with a different color
+    pub fn get_led_history(&self) -> &Vec<rgb::LedChange>
+    {
+        &self.led_history
+    }
+
+    /// Forget the LED's history, e.g. at the start of a new experiment
+    pub fn clear_led_history(&mut self)
+    {
+        self.led_history.clear();
+    }
+
+    /// Record the LED's color in its history, if it has changed since the last change recorded
+    /// # Arguments
+    /// * 'sim_ticks' - Current tick of the simulation
+    fn record_led(&mut self, sim_ticks: u32)
+    {
+        let last = self.led_history.last().map(|change| change.color).unwrap_or(rgb::RGB(0, 0, 0));
+        if self.led != last
+        {
+            self.led_history.push(rgb::LedChange { ticks: sim_ticks, color: self.led });
+        }
+    }
+
     //Returns the raw motor values formatted as (left_motor, right_motor)
     pub fn get_motor_values(&self) -> (u8, u8)
     {
@@ -89,14 +197,504 @@ impl Kilobot
         self.uid
     }
 
+    /// Returns an immutable reference to the bot's transceiver
+    pub fn get_transceiver(&self) -> &transceiver::Transceiver
+    {
+        &self.transceiver
+    }
+
+    /// Returns a mutable reference to the bot's transceiver
+    pub fn get_transceiver_mut(&mut self) -> &mut transceiver::Transceiver
+    {
+        &mut self.transceiver
+    }
+
+    /// Returns an immutable reference to the sensor the bot measures distances to other bots with
+    pub fn get_distance_sensor(&self) -> &DistanceSensor
+    {
+        &self.distance_sensor
+    }
+
+    /// Returns a mutable reference to the bot's distance sensor, e.g. to change its calibration or noise
+    pub fn get_distance_sensor_mut(&mut self) -> &mut DistanceSensor
+    {
+        &mut self.distance_sensor
+    }
+
+    /// Receive a message through the bot's transceiver. Messages with a CRC that doesn't match
+    /// their contents are dropped, same as a real kilobot. User messages are handed to the program's
+    /// message_rx callback, while system messages are handled by the bot itself
+    /// # Arguments
+    /// * 'msg' - The received message
+    /// * 'dist' - Signal strength the message was received with
+    pub fn receive_message(&mut self, msg: Message, dist: DistanceMeasurement)
+    {
+        if !msg.has_valid_crc()
+        {
+            self.transceiver.record_crc_failure();
+            return;
+        }
+        self.transceiver.record_received();
+        self.message_received = true;
+        if MessageType::is_system(msg.get_type())
+        {
+            self.process_system_message(&msg);
+        } else {
+            self.transceiver.receive(msg, dist);
+        }
+    }
+
+    /// Change the state of the bot in response to a system message, the same way kilolib does
+    /// # Arguments
+    /// * 'msg' - A message with a type of BOOT or higher
+    fn process_system_message(&mut self, msg: &Message)
+    {
+        let msg_type = match MessageType::from_u8(msg.get_type())
+        {
+            Some(t) => t,
+            None => return,
+        };
+        if !matches!(msg_type, MessageType::READUID | MessageType::RUN | MessageType::CALIB)
+        {
+            self.stop();
+        }
+        match msg_type
+        {
+            //Reprogramming isn't simulated, so the bootloader messages only stop the motors
+            MessageType::BOOT | MessageType::BOOTPGM_PAGE | MessageType::BOOTPGM_SIZE => {},
+            //Same as rebooting the bot, so the program starts again from a fresh transceiver, kilo_ticks
+            //of 0 and rand_soft's starting seed. The uid and calibration survive, as if they had been
+            //saved, and so does everything the simulation keeps about the bot itself, e.g. its pose,
+            //battery, comm stats, LED history and rand_hard stream
+            MessageType::RESET => {
+                self.set_led(0, 0, 0);
+                self.transceiver.restart();
+                self.tx_ticks = 0;
+                self.soft_rng = SoftRandom::new();
+                self.clock.restart();
+                self.kilo_state = KiloState::SETUP;
+            },
+            MessageType::SLEEP => self.kilo_state = KiloState::SLEEPING,
+            MessageType::WAKEUP => self.kilo_state = KiloState::IDLE,
+            MessageType::CHARGE => self.kilo_state = KiloState::CHARGING,
+            MessageType::VOLTAGE => self.kilo_state = KiloState::BATTERY,
+            MessageType::RUN => {
+                if !matches!(self.kilo_state, KiloState::SETUP | KiloState::RUNNING)
+                {
+                    self.kilo_state = KiloState::SETUP;
+                }
+            },
+            MessageType::READUID => self.start_moving(),
+            MessageType::CALIB => {
+                let mode = CalibMode::from_u8(msg.get_u8(0).unwrap_or(0xFF));
+                match mode
+                {
+                    Some(CalibMode::CALIB_SAVE) => {
+                        if let KiloState::MOVING = self.kilo_state
+                        {
+                            self.stop();
+                            self.kilo_state = KiloState::IDLE;
+                        }
+                    },
+                    Some(CalibMode::CALIB_UID) => {
+                        self.uid = msg.get_u16(1).unwrap_or(self.uid);
+                        self.motion = Motion::Stop;
+                    },
+                    //Same as kilolib, a new value for the movement being calibrated restarts the motors
+                    //with it, so that calibration can be tuned while the bot moves
+                    Some(CalibMode::CALIB_TURN_LEFT) => {
+                        let turn_left = msg.get_u8(3).unwrap_or(0);
+                        if turn_left != self.calibration.turn_left
+                        {
+                            self.prev_motion = Motion::Stop;
+                        }
+                        self.calibration.turn_left = turn_left;
+                        self.motion = Motion::Left;
+                    },
+                    Some(CalibMode::CALIB_TURN_RIGHT) => {
+                        let turn_right = msg.get_u8(4).unwrap_or(0);
+                        if turn_right != self.calibration.turn_right
+                        {
+                            self.prev_motion = Motion::Stop;
+                        }
+                        self.calibration.turn_right = turn_right;
+                        self.motion = Motion::Right;
+                    },
+                    Some(CalibMode::CALIB_STRAIGHT) => {
+                        let straight = (msg.get_u8(5).unwrap_or(0), msg.get_u8(6).unwrap_or(0));
+                        if straight != (self.calibration.straight_left, self.calibration.straight_right)
+                        {
+                            self.prev_motion = Motion::Stop;
+                        }
+                        self.calibration.straight_left = straight.0;
+                        self.calibration.straight_right = straight.1;
+                        self.motion = Motion::Straight;
+                    },
+                    None => {},
+                }
+                if !matches!(mode, Some(CalibMode::CALIB_SAVE))
+                {
+                    self.start_moving();
+                }
+            },
+            MessageType::NORMAL | MessageType::GPS => {},
+        }
+    }
+
+    /// Enter the MOVING state used for calibration, if the bot isn't in it already
+    fn start_moving(&mut self)
+    {
+        if let KiloState::MOVING = self.kilo_state
+        {
+            return;
+        }
+        self.set_led(0, 0, 0);
+        self.stop();
+        self.motion = Motion::Stop;
+        self.prev_motion = Motion::Stop;
+        self.kilo_state = KiloState::MOVING;
+    }
+
+    /// Ask the bot's transceiver for a message to send this tick. Same as kilolib, the program is
+    /// only asked for messages while it is RUNNING, and the transceiver keeps time with the bot's own clock
+    /// # Returns
+    /// * The message to transmit, or None if the bot has nothing to send this tick
+    pub fn poll_tx(&mut self) -> Option<Message>
+    {
+        let running = matches!(self.kilo_state, KiloState::RUNNING);
+        let ticks = std::mem::take(&mut self.tx_ticks);
+        self.transceiver.poll_tx(running, ticks)
+    }
+
+    /// Give the bot its own streams of random numbers: one used only by rand_hard, and one for the
+    /// simulation to use for the bot's sensor noise and clock. Keeping them apart means a program
+    /// gets the same rand_hard numbers however noisy its surroundings are
+    /// # Arguments
+    /// * 'seed' - Seed shared by every bot in the simulation
+    /// * 'stream' - Which of the seed's streams to use, alongside the bot's uid. Bots that share a uid
+    ///   still get independent streams as long as they are given different ones
+    pub fn seed_rng(&mut self, seed: u64, stream: u64)
+    {
+        let key = (stream << 16) | self.uid as u64;
+        self.rng = Some(Random::new_stream(seed, key << 1));
+        self.env_rng = Some(Random::new_stream(seed, (key << 1) | 1));
+    }
+
+    /// Returns whether the bot has been given its own stream of random numbers yet
+    pub fn has_seeded_rng(&self) -> bool
+    {
+        self.rng.is_some()
+    }
+
+    /// Returns the bot's stream of random numbers for rand_hard. A bot that was never seeded uses DEFAULT_SEED
+    pub fn rng_mut(&mut self) -> &mut Random
+    {
+        let uid = self.uid as u64;
+        self.rng.get_or_insert_with(|| Random::new_stream(DEFAULT_SEED, uid << 1))
+    }
+
+    /// Returns the stream of random numbers for the bot's sensor noise and clock. A bot that was never
+    /// seeded uses DEFAULT_SEED
+    pub fn env_rng_mut(&mut self) -> &mut Random
+    {
+        let uid = self.uid as u64;
+        self.env_rng.get_or_insert_with(|| Random::new_stream(DEFAULT_SEED, (uid << 1) | 1))
+    }
+
+    /// Returns the state of kilolib's rand_soft generator for this bot
+    pub fn soft_rng_mut(&mut self) -> &mut SoftRandom
+    {
+        &mut self.soft_rng
+    }
+
+    /// Returns the motor calibration stored on the bot, which its programs use
+    pub fn get_calibration(&self) -> &Calibration
+    {
+        &self.calibration
+    }
+
+    /// Set the motor calibration stored on the bot, same as calibrating it with CALIB messages
+    /// # Arguments
+    /// * 'calibration' - New calibration
+    pub fn set_calibration(&mut self, calibration: Calibration)
+    {
+        self.calibration = calibration;
+    }
+
+    /// Returns the calibration the bot's motors actually need to move as intended
+    pub fn get_ideal_calibration(&self) -> &Calibration
+    {
+        &self.ideal_calibration
+    }
+
+    /// Set the calibration the bot's motors actually need, i.e. how this particular unit is built.
+    /// Defaults to every value at MOTOR_MAX_VAL, matching the default stored calibration
+    /// # Arguments
+    /// * 'calibration' - Motor values that move this bot exactly straight, or turn it exactly on the spot
+    pub fn set_ideal_calibration(&mut self, calibration: Calibration)
+    {
+        self.ideal_calibration = calibration;
+    }
+
+    /// Returns the current state of the bot's firmware
+    pub fn get_state(&self) -> &KiloState
+    {
+        &self.kilo_state
+    }
+
+    /// Set the state of the bot's firmware, e.g. to start a simulation with every bot IDLE
+    /// # Arguments
+    /// * 'state' - New state of the bot
+    pub fn set_state(&mut self, state: KiloState)
+    {
+        self.kilo_state = state;
+    }
+
+    /// Returns the battery reading of the bot, in the same units as kilolib's get_voltage()
+    pub fn get_voltage(&self) -> i16
+    {
+        self.battery.get_voltage()
+    }
+
+    /// Returns the latest reading of the ambient light sensor, same as kilolib's get_ambientlight()
+    /// # Returns
+    /// * 10-bit light reading, or SENSOR_ERROR if the bot hasn't taken a reading yet
+    pub fn get_ambient_light(&self) -> i16
+    {
+        self.ambient_light
+    }
+
+    /// Set the reading of the ambient light sensor. The simulation takes a new reading every tick
+    /// # Arguments
+    /// * 'light' - 10-bit light reading
+    pub fn set_ambient_light(&mut self, light: i16)
+    {
+        self.ambient_light = light;
+    }
+
+    /// Returns the latest reading of the temperature sensor, same as kilolib's get_temperature()
+    /// # Returns
+    /// * 10-bit temperature reading, or SENSOR_ERROR if the bot hasn't taken a reading yet
+    pub fn get_temperature(&self) -> i16
+    {
+        self.temperature
+    }
+
+    /// Set the reading of the temperature sensor. The simulation takes a new reading every tick
+    /// # Arguments
+    /// * 'temperature' - 10-bit temperature reading
+    pub fn set_temperature(&mut self, temperature: i16)
+    {
+        self.temperature = temperature;
+    }
+
+    /// Returns an immutable reference to the bot's battery
+    pub fn get_battery(&self) -> &Battery
+    {
+        &self.battery
+    }
+
+    /// Returns the bot's kilo_ticks clock
+    pub fn get_clock(&self) -> &Clock
+    {
+        &self.clock
+    }
+
+    /// Returns a mutable reference to the bot's clock, e.g. to set its drift or phase
+    pub fn get_clock_mut(&mut self) -> &mut Clock
+    {
+        &mut self.clock
+    }
+
+    /// Returns the current value of the bot's own kilo_ticks clock
+    pub fn get_kilo_ticks(&self) -> u32
+    {
+        self.clock.get_ticks()
+    }
+
+    /// Returns a mutable reference to the bot's battery, e.g. to start it partly charged
+    pub fn get_battery_mut(&mut self) -> &mut Battery
+    {
+        &mut self.battery
+    }
+
+    /// Get the current the bot is drawing from its battery right now, not counting transmissions
+    /// # Returns
+    /// * Current in mA, from the microcontroller, the motors and the LED
+    pub fn get_current_draw(&self) -> f64
+    {
+        if let KiloState::SLEEPING = self.kilo_state
+        {
+            return SLEEP_CURRENT;
+        }
+        let max = MOTOR_MAX_VAL as f64;
+        let motors = (self.left_motor as f64 + self.right_motor as f64) / max * MOTOR_CURRENT;
+        let led = (self.led.r as f64 + self.led.g as f64 + self.led.b as f64) / max * LED_CURRENT;
+        BASE_CURRENT + motors + led
+    }
+
+    /// Estimate how long the battery will last if the bot keeps doing what it is doing now
+    /// # Returns
+    /// * Time until the battery is depleted, in seconds
+    pub fn estimate_battery_life(&self) -> f64
+    {
+        self.battery.get_seconds_remaining(self.get_current_draw())
+    }
+
+    /// Set the current available from the charger the bot is on. It is only used while the bot is CHARGING
+    /// # Arguments
+    /// * 'current' - Charging current in mA, or 0 if the bot isn't on a charger
+    pub fn set_charger_current(&mut self, current: f64)
+    {
+        self.charger_current = current;
+    }
+
+    /// Returns whether the bot is charging its battery, i.e. it is CHARGING and on a charger
+    pub fn is_charging(&self) -> bool
+    {
+        matches!(self.kilo_state, KiloState::CHARGING) && self.charger_current > 0.0
+    }
+
+    /// Drain the battery by the current the bot is drawing over a length of time, and charge it if
+    /// the bot is charging
+    /// # Arguments
+    /// * 'seconds' - How long the current is drawn for
+    pub fn update_battery(&mut self, seconds: f64)
+    {
+        let current = self.get_current_draw();
+        self.battery.drain(current, seconds);
+        if self.is_charging()
+        {
+            self.battery.charge(self.charger_current, seconds);
+        }
+    }
+
+    /// Load a user program onto the bot, replacing any program already loaded.
+    /// If a program was already running, the new one starts over from setup
+    /// # Arguments
+    /// * 'program' - Program for this bot to run
+    pub fn set_program(&mut self, program: Box<dyn KilobotProgram>)
+    {
+        self.program = Some(program);
+        if let KiloState::RUNNING = self.kilo_state
+        {
+            self.kilo_state = KiloState::SETUP;
+        }
+    }
+
+    /// Run one tick of the bot's firmware, which happens once for every tick its own clock counts. A bot whose battery has run out stops, and goes into the
+    /// BATTERY state unless it is charging. Otherwise, what happens depends on the state of the bot:
+    /// * SETUP - Runs the program's setup, then moves to RUNNING and runs the program's loop_
+    /// * RUNNING - Runs the program's loop_
+    /// * IDLE - Blinks the LED green
+    /// * MOVING - Starts the motors for the movement being calibrated, whenever it changes
+    /// * BATTERY - Shows the battery level on the LED
+    /// * CHARGING - Blinks the LED red while charging, otherwise turns it off
+    /// * Any other state - Nothing
+    /// # Arguments
+    /// * 'kilo_ticks' - Current value of the bot's kilo_ticks clock
+    /// * 'sim_ticks' - Current tick of the simulation, which LED changes are recorded against so that
+    ///   they can be compared between bots
+    pub fn run(&mut self, kilo_ticks: u32, sim_ticks: u32)
+    {
+        //A bot with a flat battery can't do anything but show that it is flat, unless it is being charged
+        if self.battery.is_depleted() && !self.is_charging()
+        {
+            self.stop();
+            self.kilo_state = KiloState::BATTERY;
+        }
+        match self.kilo_state
+        {
+            KiloState::SETUP => {
+                self.run_program(kilo_ticks, true);
+                self.kilo_state = KiloState::RUNNING;
+            },
+            KiloState::RUNNING => self.run_program(kilo_ticks, false),
+            KiloState::IDLE => {
+                if idle_blink_is_on(kilo_ticks)
+                {
+                    self.set_led(0, 255, 0);
+                } else {
+                    self.set_led(0, 0, 0);
+                }
+            },
+            KiloState::CHARGING => {
+                if self.is_charging() && idle_blink_is_on(kilo_ticks)
+                {
+                    self.set_led(255, 0, 0);
+                } else {
+                    self.set_led(0, 0, 0);
+                }
+            },
+            //Same as kilolib, the motors are spun up whenever the movement changes
+            KiloState::MOVING if self.motion != self.prev_motion => {
+                self.prev_motion = self.motion;
+                if self.motion != Motion::Stop
+                {
+                    self.spinup_motors();
+                }
+                match self.motion
+                {
+                    Motion::Stop => self.stop(),
+                    Motion::Left => self.turn_left(),
+                    Motion::Right => self.turn_right(),
+                    Motion::Straight => self.move_forward(),
+                }
+            },
+            KiloState::BATTERY => {
+                let color = rgb::battery_color(self.get_voltage());
+                self.led.set(color);
+            },
+            _ => {},
+        }
+        self.tx_ticks = self.tx_ticks.saturating_add(1);
+        self.record_led(sim_ticks);
+    }
+
+    /// Run one iteration of the bot's program. Does nothing if no program is loaded
+    /// # Arguments
+    /// * 'kilo_ticks' - Current value of the bot's kilo_ticks clock
+    /// * 'setup' - Whether to call the program's setup before its loop_
+    fn run_program(&mut self, kilo_ticks: u32, setup: bool)
+    {
+        //The program is taken out while it runs so that it can borrow the rest of the bot
+        if let Some(mut program) = self.program.take()
+        {
+            {
+                let mut api = KilobotApi::new(self, kilo_ticks);
+                if setup
+                {
+                    program.setup(&mut api);
+                }
+                program.loop_(&mut api);
+            }
+            self.program = Some(program);
+        }
+    }
+
+}
+
+/// Determines whether the LED of an IDLE or CHARGING bot is lit during a tick. kilolib lights the LED for
+/// IDLE_BLINK_ON_MS and then turns it off for IDLE_BLINK_OFF_MS, which is much shorter than a tick,
+/// so the LED is shown as lit for the whole of any tick that a blink starts in
+/// # Arguments
+/// * 'kilo_ticks' - Current value of the bot's kilo_ticks clock
+fn idle_blink_is_on(kilo_ticks: u32) -> bool
+{
+    //Work in 1/TICKS_PER_SECOND ms so that ticks land on whole numbers
+    let tps = crate::simulation::TICKS_PER_SECOND as u64;
+    let period = (IDLE_BLINK_ON_MS + IDLE_BLINK_OFF_MS) as u64 * tps;
+    let start = kilo_ticks as u64 * 1000 % period;
+    start == 0 || start + 1000 > period
 }
 
 impl fmt::Display for Kilobot
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "(UID:{}, Message Received:{}, left motor:{}, right motor:{})"
+        write!(f, "(UID:{}, State:{}, Message Received:{}, left motor:{}, right motor:{})"
                , self.uid
-               , self.message_received.to_string()
+               , self.kilo_state
+               , self.message_received
                , self.left_motor
                , self.right_motor)
     }
@@ -105,5 +703,11 @@ impl fmt::Display for Kilobot
 //Create a new kilobot
 pub fn new_kilobot(uid: u16) -> Kilobot
 {
-    Kilobot {left_motor: 0, right_motor: 0, led: rgb::new_led(0, 0, 0), uid, message_received: false}
+    Kilobot {left_motor: 0, right_motor: 0, left_spinning: false, right_spinning: false,
+        motor_model: MotorModel::default(), led: rgb::new_led(0, 0, 0), led_history: Vec::new(), uid, message_received: false,
+        transceiver: transceiver::new_transceiver(),
+        distance_sensor: DistanceSensor::new(), rng: None, env_rng: None, soft_rng: SoftRandom::new(), program: None, kilo_state: KiloState::SETUP,
+        calibration: Calibration::default(), ideal_calibration: Calibration::default(), motion: Motion::Stop, prev_motion: Motion::Stop,
+        battery: Battery::default(), charger_current: 0.0,
+        ambient_light: SENSOR_ERROR, temperature: SENSOR_ERROR, clock: Clock::new(), tx_ticks: 0}
 }
@@ -0,0 +1,130 @@
+/*
+ * battery
+ * Purpose: Model the kilobot's rechargeable battery
+ *
+ * The battery drains at a rate set by how much current the bot draws: a little for the
+ * microcontroller, and more for the motors, the LED and every message sent. The voltage falls as it
+ * drains, and get_voltage() reads it through the same 10-bit ADC scale as kilolib
+ *
+ */
+
+/// Capacity of a fully charged battery, in mAh
+pub const BATTERY_CAPACITY: f64 = 160.0;
+/// get_voltage() reading of a fully charged battery
+pub const FULL_BATTERY_VOLTAGE: i16 = 720;
+/// get_voltage() reading of a depleted battery
+pub const EMPTY_BATTERY_VOLTAGE: i16 = 580;
+/// Current drawn by the microcontroller and sensors while the bot is awake, in mA
+pub const BASE_CURRENT: f64 = 5.0;
+/// Current drawn while the bot is sleeping, in mA
+pub const SLEEP_CURRENT: f64 = 0.01;
+/// Current drawn by one motor at full duty cycle, in mA
+pub const MOTOR_CURRENT: f64 = 24.0;
+/// Current drawn by one color of the LED at full brightness, in mA
+pub const LED_CURRENT: f64 = 5.0;
+/// Current drawn by the IR LED while a message is being sent, in mA
+pub const TX_CURRENT: f64 = 40.0;
+/// Typical charging current of a charger, in mA. Charges an empty battery in about two hours
+pub const DEFAULT_CHARGE_CURRENT: f64 = 80.0;
+
+/// The bot's battery
+/// # Fields
+/// * 'capacity' - Charge held when full, in mAh
+/// * 'charge' - Charge left, in mAh
+pub struct Battery
+{
+    capacity: f64,
+    charge: f64,
+}
+
+impl Battery
+{
+    /// Create a fully charged battery
+    /// # Arguments
+    /// * 'capacity' - Charge held when full, in mAh
+    pub fn new(capacity: f64) -> Battery
+    {
+        Battery { capacity, charge: capacity }
+    }
+
+    /// Get the charge left in the battery, in mAh
+    pub fn get_charge(&self) -> f64
+    {
+        self.charge
+    }
+
+    /// Get the charge left as a fraction of the capacity
+    /// # Returns
+    /// * From 0 (depleted) to 1 (full)
+    pub fn get_charge_fraction(&self) -> f64
+    {
+        if self.capacity <= 0.0
+        {
+            return 0.0;
+        }
+        self.charge / self.capacity
+    }
+
+    /// Set the charge left as a fraction of the capacity, e.g. to start an experiment half charged
+    /// # Arguments
+    /// * 'fraction' - From 0 (depleted) to 1 (full)
+    pub fn set_charge_fraction(&mut self, fraction: f64)
+    {
+        self.charge = self.capacity * fraction.clamp(0.0, 1.0);
+    }
+
+    /// Determines whether the battery has run out
+    pub fn is_depleted(&self) -> bool
+    {
+        self.charge <= 0.0
+    }
+
+    /// Draw a current from the battery for a length of time
+    /// # Arguments
+    /// * 'current' - Current drawn, in mA
+    /// * 'seconds' - How long the current is drawn for
+    pub fn drain(&mut self, current: f64, seconds: f64)
+    {
+        self.charge = (self.charge - current * seconds / 3600.0).max(0.0);
+    }
+
+    /// Put charge back into the battery, up to its capacity
+    /// # Arguments
+    /// * 'current' - Charging current, in mA
+    /// * 'seconds' - How long the battery is charged for
+    pub fn charge(&mut self, current: f64, seconds: f64)
+    {
+        self.charge = (self.charge + current * seconds / 3600.0).min(self.capacity);
+    }
+
+    /// Get how long the battery lasts at a constant current
+    /// # Arguments
+    /// * 'current' - Current drawn, in mA
+    /// # Returns
+    /// * Time until the battery is depleted, in seconds. Infinite if no current is drawn
+    pub fn get_seconds_remaining(&self, current: f64) -> f64
+    {
+        if current <= 0.0
+        {
+            return f64::INFINITY;
+        }
+        self.charge / current * 3600.0
+    }
+
+    /// Read the battery voltage, on the same scale as kilolib's get_voltage()
+    /// # Returns
+    /// * 10-bit reading, falling linearly from FULL_BATTERY_VOLTAGE to EMPTY_BATTERY_VOLTAGE as it drains
+    pub fn get_voltage(&self) -> i16
+    {
+        let range = (FULL_BATTERY_VOLTAGE - EMPTY_BATTERY_VOLTAGE) as f64;
+        EMPTY_BATTERY_VOLTAGE + (range * self.get_charge_fraction()).round() as i16
+    }
+}
+
+impl Default for Battery
+{
+    fn default() -> Self
+    {
+        Battery::new(BATTERY_CAPACITY)
+    }
+}
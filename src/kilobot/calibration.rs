@@ -0,0 +1,63 @@
+/*
+ * calibration
+ * Purpose: Motor calibration of a kilobot
+ *
+ * No two kilobots have quite the same motors, so each one is calibrated by hand with CALIB messages
+ * from the overhead controller. kilolib keeps the results in kilo_turn_left, kilo_turn_right,
+ * kilo_straight_left and kilo_straight_right, and programs use them instead of fixed motor values.
+ *
+ * The simulator gives every bot two sets of values: the calibration it has stored, and the ideal
+ * calibration that its motors actually need. A bot only moves the way its program intends when the
+ * two match, so a badly calibrated bot curves when driving straight, like a real one
+ *
+ */
+
+use crate::kilobot::MOTOR_MAX_VAL;
+
+/// Motor values for each of the kilobot's basic movements
+/// # Fields
+/// * 'turn_left' - Left motor value for turning left on the spot. Same as kilolib's kilo_turn_left
+/// * 'turn_right' - Right motor value for turning right on the spot. Same as kilolib's kilo_turn_right
+/// * 'straight_left' - Left motor value for driving straight. Same as kilolib's kilo_straight_left
+/// * 'straight_right' - Right motor value for driving straight. Same as kilolib's kilo_straight_right
+#[derive(Clone, Copy)]
+pub struct Calibration
+{
+    pub turn_left: u8,
+    pub turn_right: u8,
+    pub straight_left: u8,
+    pub straight_right: u8,
+}
+
+impl Calibration
+{
+    /// Create a new Calibration
+    /// # Arguments
+    /// * 'turn_left' - Left motor value for turning left on the spot
+    /// * 'turn_right' - Right motor value for turning right on the spot
+    /// * 'straight_left' - Left motor value for driving straight
+    /// * 'straight_right' - Right motor value for driving straight
+    pub fn new(turn_left: u8, turn_right: u8, straight_left: u8, straight_right: u8) -> Calibration
+    {
+        Calibration { turn_left, turn_right, straight_left, straight_right }
+    }
+}
+
+impl Default for Calibration
+{
+    /// A perfectly matched pair of motors, which move the bot at full speed when run flat out
+    fn default() -> Self
+    {
+        Calibration::new(MOTOR_MAX_VAL, MOTOR_MAX_VAL, MOTOR_MAX_VAL, MOTOR_MAX_VAL)
+    }
+}
+
+/// Movement a bot makes while it is being calibrated, in the MOVING state. Same as kilolib's motion_t
+#[derive(Clone, Copy, PartialEq)]
+pub enum Motion
+{
+    Stop,
+    Left,
+    Right,
+    Straight,
+}
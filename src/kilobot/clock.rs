@@ -0,0 +1,99 @@
+/*
+ * clock
+ * Purpose: Model the kilobot's own kilo_ticks clock
+ *
+ * Every kilobot counts kilo_ticks from its own oscillator, so no two bots share a clock. Each one
+ * started counting when it was switched on, which gives it a phase offset from the others, and its
+ * oscillator runs slightly fast or slow, which makes it drift further from them over time.
+ * Synchronisation algorithms (e.g. firefly-style blinking) only have something to do when both are
+ * simulated. A clock with no phase and no drift counts the same ticks as the simulation.
+ * The bot's firmware runs once for every tick its clock counts, so a fast clock can run it twice in one
+ * tick of the simulation, and a slow one skips some
+ *
+ */
+
+use std::ops::Range;
+
+/// A bot's kilo_ticks clock
+/// # Fields
+/// * 'elapsed' - Ticks counted so far, including the fraction of the tick in progress
+/// * 'drift' - How much faster the clock runs than the simulation, as a fraction. 0.01 gains a tick every 100
+/// * 'next_due' - First tick the bot hasn't run for yet
+pub struct Clock
+{
+    elapsed: f64,
+    drift: f64,
+    next_due: u32,
+}
+
+impl Clock
+{
+    /// Create a clock that counts the same ticks as the simulation
+    pub fn new() -> Clock
+    {
+        Clock { elapsed: 0.0, drift: 0.0, next_due: 0 }
+    }
+
+    /// Get the current value of the clock, same as kilolib's kilo_ticks
+    pub fn get_ticks(&self) -> u32
+    {
+        self.elapsed as u32
+    }
+
+    /// Set how far ahead of the simulation the clock is, e.g. because the bot was switched on earlier
+    /// # Arguments
+    /// * 'phase' - Ticks counted so far. Fractions of a tick shift when the next tick is counted
+    pub fn set_phase(&mut self, phase: f64)
+    {
+        self.elapsed = phase.max(0.0);
+        self.next_due = self.get_ticks();
+    }
+
+    /// Set how much faster the clock runs than the simulation. Defaults to 0
+    /// # Arguments
+    /// * 'drift' - Fraction the clock runs fast by, or negative if it runs slow. Limited to above -1
+    pub fn set_drift(&mut self, drift: f64)
+    {
+        self.drift = drift.max(-0.99);
+    }
+
+    /// Get how much faster the clock runs than the simulation, as a fraction
+    pub fn get_drift(&self) -> f64
+    {
+        self.drift
+    }
+
+    /// Take the ticks the clock has counted since they were last taken, including the current one.
+    /// The bot runs once for each of them, in order
+    /// # Returns
+    /// * The ticks due, which is empty if the clock hasn't reached a new tick yet
+    pub fn take_due_ticks(&mut self) -> Range<u32>
+    {
+        let end = self.get_ticks() + 1;
+        let due = self.next_due..end;
+        self.next_due = self.next_due.max(end);
+        due
+    }
+
+    /// Start counting from 0 again, as when the bot is switched on. The drift stays, since it comes
+    /// from the bot's oscillator
+    pub fn restart(&mut self)
+    {
+        self.elapsed = 0.0;
+        self.next_due = 0;
+    }
+
+    /// Advance the clock by one tick of the simulation
+    pub fn advance(&mut self)
+    {
+        self.elapsed += 1.0 + self.drift;
+    }
+}
+
+impl Default for Clock
+{
+    fn default() -> Self
+    {
+        Clock::new()
+    }
+}
@@ -0,0 +1,202 @@
+/*
+ * distance
+ * Purpose: Model how a kilobot measures the distance to the sender of a message
+ *
+ * A real kilobot doesn't know the distance to a sender, only how strongly it received the message.
+ * The receiver reads the signal strength twice, through a high gain and a low gain amplifier, and
+ * hands both raw readings to message_rx as a distance_measurement_t. estimate_distance() turns them
+ * back into a distance using a calibration table stored on each bot (kilo_irhigh and kilo_irlow).
+ * The high gain reading is accurate far away but saturates up close, so the low gain reading takes
+ * over when the bots are near each other
+ *
+ */
+
+pub use crate::hal::DistanceMeasurement;
+use crate::kilobot::BODY_DIAMETER;
+use crate::simulation::random::Random;
+
+/// Number of entries in each calibration table. Same as kilolib
+pub const IR_TABLE_SIZE: usize = 14;
+/// Distance between the entries of the calibration tables, in mm between the edges of the bots
+pub const IR_TABLE_STEP: f64 = 5.0;
+/// Largest value the analog to digital converter can read
+pub const ADC_MAX: i16 = 1023;
+/// Typical high gain readings at each step of the calibration table
+pub const DEFAULT_IR_HIGH: [u16; IR_TABLE_SIZE] = [980, 880, 790, 710, 640, 575, 515, 460, 410, 365, 325, 290, 260, 235];
+/// Typical low gain readings at each step of the calibration table
+pub const DEFAULT_IR_LOW: [u16; IR_TABLE_SIZE] = [620, 500, 400, 320, 255, 205, 165, 135, 110, 90, 75, 62, 52, 44];
+/// Default standard deviation of the noise on each raw reading, in ADC counts
+pub const DEFAULT_DISTANCE_NOISE: f64 = 4.0;
+
+/// The distance sensing hardware of a single bot, along with its calibration
+/// # Fields
+/// * 'ir_high' - Calibration table for the high gain reading. Same as kilolib's kilo_irhigh
+/// * 'ir_low' - Calibration table for the low gain reading. Same as kilolib's kilo_irlow
+/// * 'noise' - Standard deviation of the noise on each raw reading, in ADC counts
+pub struct DistanceSensor
+{
+    ir_high: [u16; IR_TABLE_SIZE],
+    ir_low: [u16; IR_TABLE_SIZE],
+    noise: f64,
+}
+
+impl DistanceSensor
+{
+    /// Create a sensor with the default calibration and noise
+    pub fn new() -> DistanceSensor
+    {
+        DistanceSensor { ir_high: DEFAULT_IR_HIGH, ir_low: DEFAULT_IR_LOW, noise: DEFAULT_DISTANCE_NOISE }
+    }
+
+    /// Set the calibration tables of the sensor. The sensor's actual response follows the tables,
+    /// so estimate_distance stays accurate apart from noise
+    /// # Arguments
+    /// * 'ir_high' - High gain reading every IR_TABLE_STEP mm, starting with the bots touching
+    /// * 'ir_low' - Low gain reading every IR_TABLE_STEP mm, starting with the bots touching
+    pub fn set_calibration(&mut self, ir_high: [u16; IR_TABLE_SIZE], ir_low: [u16; IR_TABLE_SIZE])
+    {
+        self.ir_high = ir_high;
+        self.ir_low = ir_low;
+    }
+
+    /// Set how noisy the raw readings are. Defaults to DEFAULT_DISTANCE_NOISE
+    /// # Arguments
+    /// * 'noise' - Standard deviation of the noise on each raw reading, in ADC counts. 0 for no noise
+    pub fn set_noise(&mut self, noise: f64)
+    {
+        self.noise = noise.max(0.0);
+    }
+
+    /// Get the standard deviation of the noise on each raw reading, in ADC counts
+    pub fn get_noise(&self) -> f64
+    {
+        self.noise
+    }
+
+    /// Take a reading of a message sent from a given distance away
+    /// # Arguments
+    /// * 'dist' - Distance between the centers of the sender and receiver, in mm
+    /// * 'rng' - Source of randomness for the noise
+    pub fn measure(&self, dist: f64, rng: &mut Random) -> DistanceMeasurement
+    {
+        let gap = dist - BODY_DIAMETER;
+        let high = response(&self.ir_high, gap) + rng.next_gaussian() * self.noise;
+        let low = response(&self.ir_low, gap) + rng.next_gaussian() * self.noise;
+        DistanceMeasurement::new(to_adc(low), to_adc(high))
+    }
+
+    /// Convert a measurement back into a distance, the same way as kilolib's estimate_distance()
+    /// # Arguments
+    /// * 'dist' - Measurement to convert
+    /// # Returns
+    /// * Estimated distance between the centers of the two bots, in mm
+    pub fn estimate_distance(&self, dist: &DistanceMeasurement) -> u8
+    {
+        estimate_distance(dist, &self.ir_high, &self.ir_low)
+    }
+}
+
+impl Default for DistanceSensor
+{
+    fn default() -> Self
+    {
+        DistanceSensor::new()
+    }
+}
+
+/// Convert a distance measurement into the distance between the centers of two bots.
+/// A port of kilolib's estimate_distance(), including its integer truncation. Where kilolib stores a
+/// result that doesn't fit in its uint8_t, this clamps it instead, so a reading outside the calibration
+/// table gives the nearest distance the table can give rather than one that has wrapped around
+/// # Arguments
+/// * 'dist' - Measurement to convert
+/// * 'ir_high' - Calibration table for the high gain reading
+/// * 'ir_low' - Calibration table for the low gain reading
+/// # Returns
+/// * Estimated distance in mm
+pub fn estimate_distance(dist: &DistanceMeasurement, ir_high: &[u16; IR_TABLE_SIZE], ir_low: &[u16; IR_TABLE_SIZE]) -> u8
+{
+    //255 means no estimate from that reading, same as kilolib
+    let mut dist_high: u8 = 255;
+    let mut dist_low: u8 = 255;
+
+    if dist.high_gain < 900
+    {
+        dist_high = if dist.high_gain > ir_high[0] as i16
+        {
+            0
+        } else {
+            let index = ir_high[1..].iter().position(|&v| dist.high_gain > v as i16).map_or(IR_TABLE_SIZE - 1, |i| i + 1);
+            interpolate(ir_high, index, dist.high_gain)
+        };
+    }
+
+    if dist.high_gain > 700
+    {
+        dist_low = if dist.low_gain > ir_low[0] as i16
+        {
+            0
+        } else {
+            match ir_low[1..].iter().position(|&v| dist.low_gain > v as i16)
+            {
+                Some(i) => interpolate(ir_low, i + 1, dist.low_gain),
+                None => 90,
+            }
+        };
+    }
+
+    let estimate = if dist_low != 255
+    {
+        if dist_high != 255
+        {
+            let high = dist.high_gain as f64;
+            (dist_high as f64 * (900.0 - high) + dist_low as f64 * (high - 700.0)) / 200.0
+        } else {
+            dist_low as f64
+        }
+    } else {
+        dist_high as f64
+    };
+    (BODY_DIAMETER + estimate).min(u8::MAX as f64) as u8
+}
+
+/// Find the distance for a reading on the line between two entries of a calibration table
+/// # Arguments
+/// * 'table' - Calibration table
+/// * 'index' - First entry the reading is larger than. The line runs from the entry before it
+/// * 'reading' - Raw reading
+/// # Returns
+/// * Distance between the edges of the bots in mm, clamped so it never reads as 255 (no estimate)
+fn interpolate(table: &[u16; IR_TABLE_SIZE], index: usize, reading: i16) -> u8
+{
+    //kilolib works in cm with a step of 0.5, then multiplies by 10 and truncates to whole mm
+    let slope = ((table[index] as i32 - table[index - 1] as i32) as f64 / 0.5) as i32;
+    if slope == 0
+    {
+        return 0;
+    }
+    let b = table[index] as f64 - slope as f64 * (index as f64 * 0.5);
+    let mm = ((reading as f64 - b) * 10.0) as i32 / slope;
+    //kilolib assigns this straight to a uint8_t, which wraps. Clamping is deliberate, see estimate_distance
+    mm.clamp(0, 254) as u8
+}
+
+/// Get the reading a sensor that matches its calibration table gives at a given distance.
+/// Readings between the steps of the table follow a straight line, and readings past either end of
+/// the table carry on along the line through the last two entries
+/// # Arguments
+/// * 'table' - Calibration table
+/// * 'gap' - Distance between the edges of the bots in mm
+fn response(table: &[u16; IR_TABLE_SIZE], gap: f64) -> f64
+{
+    let step = gap / IR_TABLE_STEP;
+    let index = step.floor().clamp(0.0, (IR_TABLE_SIZE - 2) as f64) as usize;
+    let (a, b) = (table[index] as f64, table[index + 1] as f64);
+    a + (b - a) * (step - index as f64)
+}
+
+/// Round a reading and keep it in the range of the analog to digital converter
+fn to_adc(reading: f64) -> i16
+{
+    reading.round().clamp(0.0, ADC_MAX as f64) as i16
+}
@@ -25,13 +25,19 @@
  */
  */
 
-/// Possible message types that can be sent
-/// Note that not all of the actual kilobot message types are currently included
-enum MessageType
+/// Possible message types that can be sent, with the same values as kilolib's message_type_t.
+/// Types below BOOT are user messages and are handed to the program's message_rx callback.
+/// Types from BOOT up are system messages, sent by the overhead controller to change the state of
+/// the bot, and never reach the program
+#[allow(clippy::upper_case_acronyms)]
+#[allow(non_camel_case_types)]
+pub enum MessageType
 {
     NORMAL = 0,
     GPS,
     BOOT = 0x80,
+    BOOTPGM_PAGE,
+    BOOTPGM_SIZE,
     RESET,
     SLEEP,
     WAKEUP,
@@ -42,26 +48,333 @@ enum MessageType
     CALIB,
 }
 
+impl MessageType
+{
+    /// Get the message type matching a raw type value
+    /// # Arguments
+    /// * 'msg_type' - Raw type of a message
+    /// # Returns
+    /// * The matching MessageType, or None if the value isn't one of kilolib's types
+    pub fn from_u8(msg_type: u8) -> Option<MessageType>
+    {
+        match msg_type
+        {
+            0 => Some(MessageType::NORMAL),
+            1 => Some(MessageType::GPS),
+            0x80 => Some(MessageType::BOOT),
+            0x81 => Some(MessageType::BOOTPGM_PAGE),
+            0x82 => Some(MessageType::BOOTPGM_SIZE),
+            0x83 => Some(MessageType::RESET),
+            0x84 => Some(MessageType::SLEEP),
+            0x85 => Some(MessageType::WAKEUP),
+            0x86 => Some(MessageType::CHARGE),
+            0x87 => Some(MessageType::VOLTAGE),
+            0x88 => Some(MessageType::RUN),
+            0x89 => Some(MessageType::READUID),
+            0x8A => Some(MessageType::CALIB),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a raw type value is a system message, which kilolib handles itself
+    /// # Arguments
+    /// * 'msg_type' - Raw type of a message
+    pub fn is_system(msg_type: u8) -> bool
+    {
+        msg_type >= MessageType::BOOT as u8
+    }
+}
+
+/// Modes of a CALIB message, stored in the first byte of its payload. Same as kilolib
+/// The rest of the payload is laid out like kilolib's calibmsg_t:
+/// (1) mode, (2) uid, (1) turn_left, (1) turn_right, (1) straight_left, (1) straight_right
+#[allow(clippy::upper_case_acronyms)]
+#[allow(non_camel_case_types)]
+pub enum CalibMode
+{
+    CALIB_SAVE = 0,
+    CALIB_UID,
+    CALIB_TURN_LEFT,
+    CALIB_TURN_RIGHT,
+    CALIB_STRAIGHT,
+}
+
+impl CalibMode
+{
+    /// Get the calibration mode matching a raw mode value
+    /// # Arguments
+    /// * 'mode' - Raw mode from the first byte of a CALIB message
+    /// # Returns
+    /// * The matching CalibMode, or None if the value isn't a valid mode
+    pub fn from_u8(mode: u8) -> Option<CalibMode>
+    {
+        match mode
+        {
+            0 => Some(CalibMode::CALIB_SAVE),
+            1 => Some(CalibMode::CALIB_UID),
+            2 => Some(CalibMode::CALIB_TURN_LEFT),
+            3 => Some(CalibMode::CALIB_TURN_RIGHT),
+            4 => Some(CalibMode::CALIB_STRAIGHT),
+            _ => None,
+        }
+    }
+}
+
+/// Length of a message on the wire, in bytes
+pub const MESSAGE_SIZE: usize = 12;
+/// Length of the payload of a message, in bytes
+pub const PAYLOAD_SIZE: usize = 9;
+
+/// Errors that can occur when decoding a message
+/// Doesn't carry any sort of message
+pub enum MessageError {
+    InvalidCrc,
+}
+
 /// A message that can be transmitted by the bot
 /// The message structure mimics that of the actual kilobot
 /// So each message is 12 bytes long, in three parts: the payload
 /// (9 bytes), the type (1 byte), and a CRC (2 bytes). Kilobot documentation does not layout the
-/// structure of the payload, so for the moment it is as follows:
-/// (2) src_uid, (7) data, (1) type, (2) crc.
+/// structure of the payload, so it is left up to the program. Multi-byte values in the payload
+/// are little endian, same as on the kilobot's AVR
+#[derive(Clone)]
 pub struct Message
 {
-    data: [u8; 9],
+    data: [u8; PAYLOAD_SIZE],
+    msg_type: u8,
+    msg_crc: u16,
+}
+
+/// Builds a Message one field at a time. The CRC is generated when the message is built
+/// # Fields
+/// * 'data' - Payload of the message being built
+/// * 'msg_type' - Type of the message being built
+pub struct MessageBuilder
+{
+    data: [u8; PAYLOAD_SIZE],
     msg_type: u8,
-    msg_crc: u8,
 }
 
 impl Message
 {
-    /// (NOT IMPLEMENTED!) Generate a CRC for a message
+    /// Generate a CRC for a message and store it in the message
     /// # Arguments
     /// * 'message' - CRC will be generated based on the data and type of this message
     pub fn generate_crc(msg: &mut Message)
     {
-        msg.msg_crc = 0x00;
+        msg.msg_crc = message_crc(msg);
+    }
+
+    /// Returns the CRC stored in the message
+    pub fn get_crc(&self) -> u16
+    {
+        self.msg_crc
+    }
+
+    /// Returns the type of the message
+    pub fn get_type(&self) -> u8
+    {
+        self.msg_type
+    }
+
+    /// Returns the payload of the message
+    pub fn get_data(&self) -> &[u8; PAYLOAD_SIZE]
+    {
+        &self.data
+    }
+
+    /// Read a byte from the payload
+    /// # Arguments
+    /// * 'index' - Index of the byte in the payload
+    /// # Returns
+    /// * The byte, or None if the index is past the end of the payload
+    pub fn get_u8(&self, index: usize) -> Option<u8>
+    {
+        self.data.get(index).copied()
+    }
+
+    /// Read a little endian u16 from the payload
+    /// # Arguments
+    /// * 'index' - Index of the first byte of the value in the payload
+    /// # Returns
+    /// * The value, or None if it doesn't fit in the payload
+    pub fn get_u16(&self, index: usize) -> Option<u16>
+    {
+        match (self.get_u8(index), self.get_u8(index + 1))
+        {
+            (Some(low), Some(high)) => Some(u16::from_le_bytes([low, high])),
+            _ => None,
+        }
+    }
+
+    /// Read a little endian i16 from the payload
+    /// # Arguments
+    /// * 'index' - Index of the first byte of the value in the payload
+    /// # Returns
+    /// * The value, or None if it doesn't fit in the payload
+    pub fn get_i16(&self, index: usize) -> Option<i16>
+    {
+        self.get_u16(index).map(|value| value as i16)
+    }
+
+    /// Encode the message in the same 12 byte layout as kilolib's message_t:
+    /// (9) payload, (1) type, (2) CRC, little endian
+    pub fn to_bytes(&self) -> [u8; MESSAGE_SIZE]
+    {
+        let mut bytes = [0; MESSAGE_SIZE];
+        bytes[..PAYLOAD_SIZE].copy_from_slice(&self.data);
+        bytes[PAYLOAD_SIZE] = self.msg_type;
+        bytes[PAYLOAD_SIZE + 1..].copy_from_slice(&self.msg_crc.to_le_bytes());
+        bytes
+    }
+
+    /// Decode a message from the same 12 byte layout as kilolib's message_t
+    /// # Arguments
+    /// * 'bytes' - Encoded message
+    /// # Returns
+    /// * Ok - The decoded message
+    /// * Err - MessageError if the CRC doesn't match the rest of the message
+    pub fn from_bytes(bytes: &[u8; MESSAGE_SIZE]) -> Result<Message, MessageError>
+    {
+        let mut data = [0; PAYLOAD_SIZE];
+        data.copy_from_slice(&bytes[..PAYLOAD_SIZE]);
+        let msg = Message {
+            data,
+            msg_type: bytes[PAYLOAD_SIZE],
+            msg_crc: u16::from_le_bytes([bytes[PAYLOAD_SIZE + 1], bytes[PAYLOAD_SIZE + 2]]),
+        };
+        if msg.has_valid_crc()
+        {
+            Ok(msg)
+        } else {
+            Err(MessageError::InvalidCrc)
+        }
+    }
+
+    /// Flip a single bit of the message as it is sent on the wire, e.g. to simulate interference.
+    /// The CRC isn't updated, so unless the flipped bit is in the CRC itself the message no longer
+    /// passes has_valid_crc()
+    /// # Arguments
+    /// * 'bit' - Bit to flip, counting from the lowest bit of the first byte of to_bytes().
+    ///   Wraps around past the end of the message
+    pub fn flip_bit(&mut self, bit: usize)
+    {
+        let bit = bit % (MESSAGE_SIZE * 8);
+        let mut bytes = self.to_bytes();
+        bytes[bit / 8] ^= 1 << (bit % 8);
+        self.data.copy_from_slice(&bytes[..PAYLOAD_SIZE]);
+        self.msg_type = bytes[PAYLOAD_SIZE];
+        self.msg_crc = u16::from_le_bytes([bytes[PAYLOAD_SIZE + 1], bytes[PAYLOAD_SIZE + 2]]);
+    }
+
+    /// Returns whether the CRC stored in the message matches its data and type.
+    /// Messages that fail this check were corrupted and are dropped by the receiver
+    pub fn has_valid_crc(&self) -> bool
+    {
+        self.msg_crc == message_crc(self)
+    }
+}
+
+impl MessageBuilder
+{
+    /// Start building a message with an empty payload
+    /// # Arguments
+    /// * 'msg_type' - Type of the message. User messages should use a type between 0 and 127
+    pub fn new(msg_type: u8) -> MessageBuilder
+    {
+        MessageBuilder { data: [0; PAYLOAD_SIZE], msg_type }
+    }
+
+    /// Set the type of the message
+    /// # Arguments
+    /// * 'msg_type' - New type of the message
+    pub fn msg_type(mut self, msg_type: u8) -> MessageBuilder
+    {
+        self.msg_type = msg_type;
+        self
     }
-}
\ No newline at end of file
+
+    /// Set the whole payload of the message
+    /// # Arguments
+    /// * 'data' - New payload
+    pub fn data(mut self, data: [u8; PAYLOAD_SIZE]) -> MessageBuilder
+    {
+        self.data = data;
+        self
+    }
+
+    /// Write a byte into the payload.
+    /// !! Panics if the index is past the end of the payload !!
+    /// # Arguments
+    /// * 'index' - Index of the byte in the payload
+    /// * 'value' - Value to write
+    pub fn u8_at(mut self, index: usize, value: u8) -> MessageBuilder
+    {
+        self.data[index] = value;
+        self
+    }
+
+    /// Write a little endian u16 into the payload.
+    /// !! Panics if the value doesn't fit in the payload !!
+    /// # Arguments
+    /// * 'index' - Index of the first byte of the value in the payload
+    /// * 'value' - Value to write
+    pub fn u16_at(mut self, index: usize, value: u16) -> MessageBuilder
+    {
+        self.data[index..index + 2].copy_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Write a little endian i16 into the payload.
+    /// !! Panics if the value doesn't fit in the payload !!
+    /// # Arguments
+    /// * 'index' - Index of the first byte of the value in the payload
+    /// * 'value' - Value to write
+    pub fn i16_at(self, index: usize, value: i16) -> MessageBuilder
+    {
+        self.u16_at(index, value as u16)
+    }
+
+    /// Finish the message and generate its CRC
+    pub fn build(self) -> Message
+    {
+        let mut msg = Message { data: self.data, msg_type: self.msg_type, msg_crc: 0 };
+        Message::generate_crc(&mut msg);
+        msg
+    }
+}
+
+//Create a new message with a CRC generated from its type and data
+pub fn new_message(msg_type: u8, data: [u8; PAYLOAD_SIZE]) -> Message
+{
+    MessageBuilder::new(msg_type).data(data).build()
+}
+
+/// Calculate the CRC of a message the same way as kilolib's message_crc(). The CRC covers the
+/// data followed by the type, starting from 0xFFFF
+/// # Arguments
+/// * 'msg' - Message to calculate the CRC of. The CRC already stored in it is ignored
+/// # Returns
+/// * The 16 bit CRC
+pub fn message_crc(msg: &Message) -> u16
+{
+    let mut crc: u16 = 0xFFFF;
+    for byte in msg.data.iter()
+    {
+        crc = crc_ccitt_update(crc, *byte);
+    }
+    crc_ccitt_update(crc, msg.msg_type)
+}
+
+/// Add a byte to a CRC-CCITT. Bit-exact port of avr-libc's _crc_ccitt_update(), which kilolib uses
+/// # Arguments
+/// * 'crc' - CRC of the bytes so far
+/// * 'data' - Next byte
+/// # Returns
+/// * The updated CRC
+pub fn crc_ccitt_update(crc: u16, data: u8) -> u16
+{
+    let mut data = data ^ (crc & 0xFF) as u8;
+    data ^= data << 4;
+    (((data as u16) << 8) | (crc >> 8)) ^ (data >> 4) as u16 ^ ((data as u16) << 3)
+}
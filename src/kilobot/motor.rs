@@ -0,0 +1,89 @@
+/*
+ * motor
+ * Purpose: Model how the kilobot's vibration motors respond to their duty cycle
+ *
+ * The motors don't respond linearly. A motor at rest has to overcome static friction, so it only
+ * starts at a high duty cycle, which is why kilolib programs call spinup_motors() (a brief burst at
+ * full power) before setting a low duty cycle. Once it is spinning it keeps going at lower duty cycles,
+ * but stalls if the duty cycle drops too far. Above the stall point, speed rises steeply at first and
+ * then levels off
+ *
+ */
+
+use crate::kilobot::MOTOR_MAX_VAL;
+
+/// Default lowest duty cycle that starts a motor from rest
+pub const DEFAULT_START_THRESHOLD: u8 = 100;
+/// Default duty cycle below which a spinning motor stalls
+pub const DEFAULT_STALL_THRESHOLD: u8 = 40;
+/// Default exponent of the response curve. Below 1 the curve levels off at high duty cycles
+pub const DEFAULT_RESPONSE_EXPONENT: f64 = 0.5;
+
+/// How a motor responds to its duty cycle
+/// # Fields
+/// * 'start_threshold' - Lowest duty cycle that starts the motor from rest
+/// * 'stall_threshold' - Duty cycle below which a spinning motor stalls
+/// * 'exponent' - Shape of the response curve above the stall threshold. 1 is linear
+pub struct MotorModel
+{
+    pub start_threshold: u8,
+    pub stall_threshold: u8,
+    pub exponent: f64,
+}
+
+impl MotorModel
+{
+    /// Create a new MotorModel
+    /// # Arguments
+    /// * 'start_threshold' - Lowest duty cycle that starts the motor from rest
+    /// * 'stall_threshold' - Duty cycle below which a spinning motor stalls
+    /// * 'exponent' - Shape of the response curve above the stall threshold. 1 is linear
+    pub fn new(start_threshold: u8, stall_threshold: u8, exponent: f64) -> MotorModel
+    {
+        MotorModel { start_threshold, stall_threshold, exponent }
+    }
+
+    /// A motor that responds instantly and in proportion to its duty cycle, with no need to spin up
+    pub fn linear() -> MotorModel
+    {
+        MotorModel::new(0, 0, 1.0)
+    }
+
+    /// Determines whether a motor keeps spinning after its duty cycle changes
+    /// # Arguments
+    /// * 'spinning' - Whether the motor was spinning before the change
+    /// * 'duty' - New duty cycle
+    pub fn is_spinning(&self, spinning: bool, duty: u8) -> bool
+    {
+        if duty == 0 || duty < self.stall_threshold
+        {
+            false
+        } else {
+            spinning || duty >= self.start_threshold
+        }
+    }
+
+    /// Gets the speed of a spinning motor
+    /// # Arguments
+    /// * 'duty' - Duty cycle of the motor
+    /// # Returns
+    /// * Speed as a fraction of the speed at MOTOR_MAX_VAL, from 0 to 1
+    pub fn response(&self, duty: u8) -> f64
+    {
+        if duty == 0 || duty < self.stall_threshold
+        {
+            return 0.0;
+        }
+        let range = (MOTOR_MAX_VAL - self.stall_threshold.min(MOTOR_MAX_VAL - 1)) as f64;
+        let above = (duty - self.stall_threshold) as f64;
+        (above / range).powf(self.exponent).min(1.0)
+    }
+}
+
+impl Default for MotorModel
+{
+    fn default() -> Self
+    {
+        MotorModel::new(DEFAULT_START_THRESHOLD, DEFAULT_STALL_THRESHOLD, DEFAULT_RESPONSE_EXPONENT)
+    }
+}
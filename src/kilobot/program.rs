@@ -0,0 +1,172 @@
+/*
+ * program
+ * Purpose: Define the user program that runs on a kilobot, and the kilolib functions it can call
+ *
+ * Mirrors the structure of a kilolib program:
+ *      void setup() { ... }
+ *      void loop() { ... }
+ *      int main() {
+ *          kilo_init();
+ *          kilo_message_rx = message_rx;
+ *          kilo_start(setup, loop);
+ *      }
+ * Global variables in a C program become fields on the type implementing KilobotProgram, so every
+ * bot gets its own copy of them. State that the transceiver callbacks need as well goes in an
+ * Rc<RefCell<...>> field, with a clone of it captured by each callback closure
+ *
+ */
+
+use crate::hal::{Hal, DistanceMeasurement, MessageTxCallback, MessageRxCallback, EventCallback};
+use crate::kilobot::Kilobot;
+use crate::kilobot::messages::Message;
+use crate::kilobot::rgb::RGB;
+
+/// A user program that controls a single kilobot
+/// Each bot owns its own instance, so any state the program needs can be kept in the implementing type
+pub trait KilobotProgram
+{
+    /// Called once when the program starts, before the first call to loop_
+    /// # Arguments
+    /// * 'api' - kilolib functions and variables of the bot running the program
+    fn setup(&mut self, api: &mut dyn Hal);
+
+    /// Called repeatedly for as long as the program is running. Named loop_ since loop is a keyword
+    /// # Arguments
+    /// * 'api' - kilolib functions and variables of the bot running the program
+    fn loop_(&mut self, api: &mut dyn Hal);
+}
+
+/// The simulator's implementation of Hal, handed to a user program while it is running
+/// # Fields
+/// * 'bot' - The bot running the program
+/// * 'kilo_ticks' - Value of the kilo_ticks clock when the program was called
+pub struct KilobotApi<'a>
+{
+    bot: &'a mut Kilobot,
+    kilo_ticks: u32,
+}
+
+impl<'a> KilobotApi<'a>
+{
+    /// Create an api for a bot
+    /// # Arguments
+    /// * 'bot' - Bot the program is running on
+    /// * 'kilo_ticks' - Current value of the kilo_ticks clock
+    pub fn new(bot: &'a mut Kilobot, kilo_ticks: u32) -> KilobotApi<'a>
+    {
+        KilobotApi { bot, kilo_ticks }
+    }
+}
+
+impl<'a> Hal for KilobotApi<'a>
+{
+    fn set_motors(&mut self, left: u8, right: u8)
+    {
+        self.bot.set_motors(left, right);
+    }
+
+    fn spinup_motors(&mut self)
+    {
+        self.bot.spinup_motors();
+    }
+
+    fn set_color(&mut self, color: RGB)
+    {
+        self.bot.set_led(color.r, color.g, color.b);
+    }
+
+    fn kilo_uid(&self) -> u16
+    {
+        self.bot.get_uid()
+    }
+
+    fn kilo_ticks(&self) -> u32
+    {
+        self.kilo_ticks
+    }
+
+    fn kilo_turn_left(&self) -> u8
+    {
+        self.bot.get_calibration().turn_left
+    }
+
+    fn kilo_turn_right(&self) -> u8
+    {
+        self.bot.get_calibration().turn_right
+    }
+
+    fn kilo_straight_left(&self) -> u8
+    {
+        self.bot.get_calibration().straight_left
+    }
+
+    fn kilo_straight_right(&self) -> u8
+    {
+        self.bot.get_calibration().straight_right
+    }
+
+    fn set_message_rx(&mut self, cb: MessageRxCallback)
+    {
+        self.bot.transceiver.set_rx_callback(cb);
+    }
+
+    fn set_message_rx_success(&mut self, cb: EventCallback)
+    {
+        self.bot.transceiver.set_rx_success_callback(cb);
+    }
+
+    fn set_message_tx(&mut self, cb: MessageTxCallback)
+    {
+        self.bot.transceiver.set_tx_callback(cb);
+    }
+
+    fn set_message_tx_success(&mut self, cb: EventCallback)
+    {
+        self.bot.transceiver.set_tx_success_callback(cb);
+    }
+
+    fn queue_message(&mut self, msg: Message) -> bool
+    {
+        self.bot.transceiver.queue_message(msg)
+    }
+
+    fn set_kilo_tx_period(&mut self, period: u32)
+    {
+        self.bot.transceiver.set_tx_period(period);
+    }
+
+    fn estimate_distance(&self, dist: &DistanceMeasurement) -> u8
+    {
+        self.bot.get_distance_sensor().estimate_distance(dist)
+    }
+
+    fn rand_hard(&mut self) -> u8
+    {
+        self.bot.rng_mut().next_u64() as u8
+    }
+
+    fn rand_soft(&mut self) -> u8
+    {
+        self.bot.soft_rng_mut().rand_soft()
+    }
+
+    fn rand_seed(&mut self, seed: u8)
+    {
+        self.bot.soft_rng_mut().rand_seed(seed);
+    }
+
+    fn get_ambientlight(&self) -> i16
+    {
+        self.bot.get_ambient_light()
+    }
+
+    fn get_voltage(&self) -> i16
+    {
+        self.bot.get_voltage()
+    }
+
+    fn get_temperature(&self) -> i16
+    {
+        self.bot.get_temperature()
+    }
+}
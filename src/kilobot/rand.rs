@@ -0,0 +1,59 @@
+/*
+ * rand
+ * Purpose: kilolib's software random number generator
+ *
+ * Ported as-is from kilolib so that programs calling rand_soft() get the same sequence they would
+ * on a real bot:
+ *      seed ^= seed<<3;
+ *      seed ^= seed>>5;
+ *      seed ^= accumulator++>>2;
+ *
+ */
+
+/// Seed rand_soft starts from until rand_seed is called. Same as kilolib
+pub const DEFAULT_SOFT_SEED: u8 = 0xaa;
+
+/// State of kilolib's rand_soft generator
+/// # Fields
+/// * 'seed' - Current state of the generator, set by rand_seed
+/// * 'accumulator' - Counts the calls to rand_soft, and is mixed into every result
+pub struct SoftRandom
+{
+    seed: u8,
+    accumulator: u8,
+}
+
+impl SoftRandom
+{
+    /// Create a generator in the same state as a freshly started kilobot
+    pub fn new() -> SoftRandom
+    {
+        SoftRandom { seed: DEFAULT_SOFT_SEED, accumulator: 0 }
+    }
+
+    /// Get the next number from the generator. Same as kilolib's rand_soft()
+    pub fn rand_soft(&mut self) -> u8
+    {
+        self.seed ^= self.seed << 3;
+        self.seed ^= self.seed >> 5;
+        self.seed ^= self.accumulator >> 2;
+        self.accumulator = self.accumulator.wrapping_add(1);
+        self.seed
+    }
+
+    /// Set the seed of the generator. Same as kilolib's rand_seed(seed)
+    /// # Arguments
+    /// * 'seed' - New seed
+    pub fn rand_seed(&mut self, seed: u8)
+    {
+        self.seed = seed;
+    }
+}
+
+impl Default for SoftRandom
+{
+    fn default() -> Self
+    {
+        SoftRandom::new()
+    }
+}
@@ -1,6 +1,13 @@
 pub const LED_OFF: (u8, u8, u8) = (0, 0, 0);
+/// Highest brightness level of each LED channel. kilolib gives each channel 2 bits, so levels run 0-3
+pub const MAX_LEVEL: u8 = 3;
+/// Channel value of one brightness level, so that MAX_LEVEL is a full 255
+pub const LEVEL_STEP: u8 = 85;
 
-/// Struct representing the kilobot LED
+/// Struct representing the kilobot LED. Each channel is 0-255, but the LED can only show the four
+/// brightness levels of kilolib's 2-bit channels, so a color set on the LED is rounded to the nearest level
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct RGB
 {
     pub r: u8,
@@ -10,11 +17,12 @@ pub struct RGB
 
 impl RGB
 {
-    /// Set the value of an LED
+    /// Set the value of an LED, rounded to the colors the LED can show
     /// # Arguments
     /// * 'color' - RGB values to set LED to
     pub fn set(&mut self, color: RGB)
     {
+        let color = color.quantized();
         self.r = color.r;
         self.g = color.g;
         self.b = color.b;
@@ -26,10 +34,89 @@ impl RGB
         self
     }
 
+    /// Get the brightness level of each channel
+    /// # Returns
+    /// * Levels from 0 to MAX_LEVEL, formatted as (r, g, b)
+    pub fn get_levels(&self) -> (u8, u8, u8)
+    {
+        (to_level(self.r), to_level(self.g), to_level(self.b))
+    }
+
+    /// Get the nearest color the LED can show
+    pub fn quantized(&self) -> RGB
+    {
+        let (r, g, b) = self.get_levels();
+        RGB(r, g, b)
+    }
+
+    /// Pack the color into a byte, the way kilolib's RGB(r,g,b) macro does
+    /// # Returns
+    /// * Red in bits 0-1, green in bits 2-3 and blue in bits 4-5
+    pub fn as_byte(&self) -> u8
+    {
+        let (r, g, b) = self.get_levels();
+        r | (g << 2) | (b << 4)
+    }
+
+    /// Unpack a color packed by kilolib's RGB(r,g,b) macro
+    /// # Arguments
+    /// * 'byte' - Red in bits 0-1, green in bits 2-3 and blue in bits 4-5
+    pub fn from_byte(byte: u8) -> RGB
+    {
+        RGB(byte, byte >> 2, byte >> 4)
+    }
+}
+
+/// Create a color from kilolib brightness levels. Same as kilolib's RGB(r,g,b) macro,
+/// so kilolib programs can keep writing e.g. set_color(RGB(1,0,0)) for a dim red
+/// # Arguments
+/// * 'r' - Red level, from 0 to MAX_LEVEL. Higher bits are ignored, same as kilolib
+/// * 'g' - Green level, from 0 to MAX_LEVEL
+/// * 'b' - Blue level, from 0 to MAX_LEVEL
+#[allow(non_snake_case)]
+pub fn RGB(r: u8, g: u8, b: u8) -> RGB
+{
+    RGB { r: (r & MAX_LEVEL) * LEVEL_STEP, g: (g & MAX_LEVEL) * LEVEL_STEP, b: (b & MAX_LEVEL) * LEVEL_STEP }
+}
+
+/// Round a 0-255 channel value to the nearest brightness level
+fn to_level(value: u8) -> u8
+{
+    ((value as u16 + LEVEL_STEP as u16 / 2) / LEVEL_STEP as u16) as u8
+}
+
+/// A change in the color of a bot's LED
+/// # Fields
+/// * 'ticks' - Simulation tick the LED changed on. Bots' own clocks differ, so this is what they share
+/// * 'color' - Color the LED changed to
+#[derive(Clone, Copy)]
+pub struct LedChange
+{
+    pub ticks: u32,
+    pub color: RGB,
 }
 
 //Create a new LED
 pub fn new_led(r: u8, g: u8, b: u8) -> RGB
 {
     RGB {r, g, b}
-}
\ No newline at end of file
+}
+
+/// Get the color kilolib shows on the LED in the BATTERY state
+/// # Arguments
+/// * 'voltage' - Battery reading, in the same units as kilolib's get_voltage()
+/// # Returns
+/// * Green when nearly full, then blue, then yellow, and red when nearly empty
+pub fn battery_color(voltage: i16) -> RGB
+{
+    if voltage > 682
+    {
+        new_led(0, 255, 0)
+    } else if voltage > 648 {
+        new_led(0, 0, 255)
+    } else if voltage > 614 {
+        new_led(255, 255, 0)
+    } else {
+        new_led(255, 0, 0)
+    }
+}
@@ -0,0 +1,58 @@
+/*
+ * state
+ * Purpose: Define the states of the kilobot firmware
+ *
+ * Mirrors kilolib's kilo_state. The state decides what the bot does every tick, and system
+ * messages from the overhead controller are what move the bot between states
+ *
+ */
+
+use std::fmt;
+
+/// State of the kilobot firmware. Same as kilolib's kilo_state
+///
+/// Transitions, all made by system messages except where noted:
+/// * SETUP -> RUNNING - Automatically, once the program's setup has run
+/// * Any state -> SETUP - RESET
+/// * RUNNING -> SETUP - Loading a new program
+/// * Any state -> SLEEPING/CHARGING/BATTERY - SLEEP/CHARGE/VOLTAGE
+/// * Any state -> IDLE - WAKEUP, or CALIB_SAVE when MOVING
+/// * Any state other than SETUP/RUNNING -> SETUP - RUN
+/// * Any state -> MOVING - READUID or CALIB
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum KiloState
+{
+    /// Low power mode. Nothing runs until a WAKEUP message arrives
+    SLEEPING,
+    /// Waiting for a command, with the program stopped
+    IDLE,
+    /// Showing the battery level on the LED
+    BATTERY,
+    /// About to run the program's setup, followed by its loop
+    SETUP,
+    /// Running the program's loop
+    RUNNING,
+    /// Sitting on a charger
+    CHARGING,
+    /// Being calibrated or identified by the overhead controller
+    MOVING,
+}
+
+impl fmt::Display for KiloState
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let name = match self
+        {
+            KiloState::SLEEPING => "SLEEPING",
+            KiloState::IDLE => "IDLE",
+            KiloState::BATTERY => "BATTERY",
+            KiloState::SETUP => "SETUP",
+            KiloState::RUNNING => "RUNNING",
+            KiloState::CHARGING => "CHARGING",
+            KiloState::MOVING => "MOVING",
+        };
+        write!(f, "{}", name)
+    }
+}
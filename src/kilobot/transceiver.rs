@@ -16,34 +16,395 @@
  * detecting any contention in the channel.
  * ------------------------------------------------------------------
  */
+use std::collections::VecDeque;
+use std::fmt;
 use crate::kilobot::messages::Message;
+use crate::hal::{DistanceMeasurement, MessageTxCallback, MessageRxCallback, EventCallback};
+use crate::simulation::random::Random;
+
+/// Default number of ticks between transmissions, which is twice per second at 32 ticks/sec
+pub const DEFAULT_TX_PERIOD: u32 = 16;
+/// Largest power of two the back-off window grows to after repeated failed transmissions.
+/// The window never gets longer than 2^MAX_BACKOFF_EXPONENT ticks
+pub const MAX_BACKOFF_EXPONENT: u32 = 4;
+/// Default number of messages the outbox holds before it starts dropping new ones
+pub const DEFAULT_OUTBOX_CAPACITY: usize = 8;
+
+/// Counts of what happened to the messages a transceiver sent and received
+/// # Fields
+/// * 'attempted' - Transmissions attempted, including ones that failed
+/// * 'transmitted' - Transmissions sent without detecting any contention
+/// * 'collided' - Transmissions lost because another bot in range transmitted at the same time
+/// * 'channel_busy' - Transmissions never sent, because carrier sensing found the channel in use
+/// * 'crc_failures' - Messages received corrupted, and dropped because their CRC didn't match
+/// * 'received' - Messages received intact, including ones from the overhead controller
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct CommStats
+{
+    pub attempted: u32,
+    pub transmitted: u32,
+    pub collided: u32,
+    pub channel_busy: u32,
+    pub crc_failures: u32,
+    pub received: u32,
+}
+
+impl CommStats
+{
+    /// Add the counts of another set of stats to these ones
+    /// # Arguments
+    /// * 'other' - Stats to add
+    pub fn add(&mut self, other: &CommStats)
+    {
+        self.attempted += other.attempted;
+        self.transmitted += other.transmitted;
+        self.collided += other.collided;
+        self.channel_busy += other.channel_busy;
+        self.crc_failures += other.crc_failures;
+        self.received += other.received;
+    }
+
+    /// Get the fraction of attempted transmissions that were sent without contention
+    /// # Returns
+    /// * From 0 to 1, or 0 if nothing has been attempted
+    pub fn get_success_rate(&self) -> f64
+    {
+        if self.attempted == 0
+        {
+            return 0.0;
+        }
+        self.transmitted as f64 / self.attempted as f64
+    }
+}
+
+impl fmt::Display for CommStats
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(attempted:{}, transmitted:{}, collided:{}, channel busy:{}, CRC failures:{}, received:{})"
+               , self.attempted
+               , self.transmitted
+               , self.collided
+               , self.channel_busy
+               , self.crc_failures
+               , self.received)
+    }
+}
 
 /// The kilobot's transceiver, which operates using callbacks
 /// # Fields
 /// * 'message_received' - 0 if no message received, 1 if message received. Type is u8 to reflect
-/// actual kilobot code
+///   actual kilobot code
 /// * 'message_tx' - Callback function that is called whenever a message is ready to be transmitted. Returns
-/// a message object to be sent, or null if no message should be sent
+///   a message object to be sent, or null if no message should be sent
 /// * 'message_rx' - Callback function that is called whenever a message is received. Takes a message
-/// object and the measured distance from the source
+///   object and the signal strength it was received with, which estimate_distance turns into a distance
+/// * 'message_tx_success' - Callback function that is called after a message is successfully transmitted
+/// * 'message_rx_success' - Callback function that is called after message_rx has handled a message
+/// * 'outbox' - Messages queued to be sent, oldest first. Sent before asking message_tx for a message
+/// * 'outbox_capacity' - Most messages the outbox holds. Messages queued while it is full are dropped
+/// * 'sending_from_outbox' - Whether the message last handed out by poll_tx came from the outbox
+/// * 'dropped' - Number of messages dropped because the outbox was full
+/// * 'unreported_drops' - Number of those drops the simulation hasn't reported yet
+/// * 'tx_period' - Number of ticks to wait after a transmission before trying to transmit again.
+///   Same as kilolib's kilo_tx_period
+/// * 'tx_clock' - Number of ticks the bot's clock has counted since the last transmission
+/// * 'backoff' - Number of the bot's ticks left to wait before trying to transmit again after a failure
+/// * 'failed_attempts' - Number of transmissions in a row that have failed. Each failure doubles
+///   the back-off window
+/// * 'stats' - Counts of what happened to the messages sent and received, kept up to date by the simulation
 /// # Notes
 /// * There is no 'ack' response, a message is transmitted only if there is no contention
+/// * Callbacks are closures owned by this bot's transceiver, so they can capture state of their own.
+///   To share state with the program, capture a clone of an Rc<RefCell<...>> that the program also keeps
 pub struct Transceiver
 {
     message_received: u8,
-    message_tx: fn() -> Option<Message>,
-    message_rx: fn(msg: Message, dist: u16),
-    message_tx_success: fn(),
-    message_rx_success: fn(),
+    message_tx: MessageTxCallback,
+    message_rx: MessageRxCallback,
+    message_tx_success: EventCallback,
+    message_rx_success: EventCallback,
+    outbox: VecDeque<Message>,
+    outbox_capacity: usize,
+    sending_from_outbox: bool,
+    dropped: u32,
+    unreported_drops: u32,
+    tx_period: u32,
+    tx_clock: u32,
+    backoff: u32,
+    failed_attempts: u32,
+    stats: CommStats,
 }
 
 impl Transceiver
 {
+    /// Sets the callback function to be run when a message is received
+    /// # Arguments
+    /// * 'cb' - Function called with the received message and the signal strength it was received with
+    pub fn set_rx_callback(&mut self, cb: MessageRxCallback)
+    {
+        self.message_rx = cb
+    }
+
+    /// Sets the callback function to be run after message_rx has handled a received message
+    /// # Arguments
+    /// * 'cb' - Function called after each message is received
+    pub fn set_rx_success_callback(&mut self, cb: EventCallback)
+    {
+        self.message_rx_success = cb
+    }
+
+    /// Sets the callback function to be run after a message is successfully transmitted
+    /// # Arguments
+    /// * 'cb' - Function called after a successful transmission
+    pub fn set_tx_success_callback(&mut self, cb: EventCallback)
+    {
+        self.message_tx_success = cb
+    }
+
     /// Sets the callback function to be run when a message is ready to be transmitted
     /// # Arguments
     /// * 'cb' - Function called to check if a message is ready
-    pub fn set_tx_callback(&mut self, cb : fn() -> Option<Message>)
+    pub fn set_tx_callback(&mut self, cb: MessageTxCallback)
     {
         self.message_tx = cb
     }
-}
\ No newline at end of file
+
+    /// Set the number of ticks to wait after a transmission before transmitting again. Same as setting
+    /// kilolib's kilo_tx_period. Defaults to DEFAULT_TX_PERIOD
+    /// # Arguments
+    /// * 'period' - Ticks between transmissions. 0 is treated as 1, since only one message fits in a tick
+    pub fn set_tx_period(&mut self, period: u32)
+    {
+        self.tx_period = period.max(1);
+    }
+
+    /// Get the number of ticks the transceiver waits after a transmission before transmitting again
+    pub fn get_tx_period(&self) -> u32
+    {
+        self.tx_period
+    }
+
+    /// Queue a message to be sent. Queued messages are sent one per transmission, oldest first, and
+    /// each stays queued until it is sent without contention. message_tx is only asked for a message
+    /// while the outbox is empty
+    /// # Arguments
+    /// * 'msg' - Message to send
+    /// # Returns
+    /// * true if the message was queued, false if the outbox was full and the message was dropped
+    pub fn queue_message(&mut self, msg: Message) -> bool
+    {
+        if self.outbox.len() >= self.outbox_capacity
+        {
+            self.dropped = self.dropped.saturating_add(1);
+            self.unreported_drops = self.unreported_drops.saturating_add(1);
+            return false;
+        }
+        self.outbox.push_back(msg);
+        true
+    }
+
+    /// Set the most messages the outbox holds. Defaults to DEFAULT_OUTBOX_CAPACITY. If the outbox already
+    /// holds more, the newest messages are dropped
+    /// # Arguments
+    /// * 'capacity' - Most messages the outbox holds
+    pub fn set_outbox_capacity(&mut self, capacity: usize)
+    {
+        self.outbox_capacity = capacity;
+        while self.outbox.len() > capacity
+        {
+            self.outbox.pop_back();
+            self.dropped = self.dropped.saturating_add(1);
+            self.unreported_drops = self.unreported_drops.saturating_add(1);
+        }
+    }
+
+    /// Get the most messages the outbox holds
+    pub fn get_outbox_capacity(&self) -> usize
+    {
+        self.outbox_capacity
+    }
+
+    /// Get the messages waiting in the outbox, oldest first
+    pub fn get_outbox(&self) -> &VecDeque<Message>
+    {
+        &self.outbox
+    }
+
+    /// Remove every message waiting in the outbox, without counting them as dropped
+    pub fn clear_outbox(&mut self)
+    {
+        self.outbox.clear();
+        self.sending_from_outbox = false;
+    }
+
+    /// Get the number of messages dropped because the outbox was full, since the transceiver was created
+    pub fn get_dropped_count(&self) -> u32
+    {
+        self.dropped
+    }
+
+    /// Get the number of messages dropped since the last call, so that the simulation can report them
+    pub(crate) fn take_unreported_drops(&mut self) -> u32
+    {
+        std::mem::take(&mut self.unreported_drops)
+    }
+
+    /// Get the counts of what happened to the messages this transceiver sent and received
+    pub fn get_stats(&self) -> &CommStats
+    {
+        &self.stats
+    }
+
+    /// Put the transceiver back the way it was when the bot was switched on, e.g. when the bot is reset.
+    /// The callbacks, outbox and tx_period go back to their defaults, but the stats are kept, since
+    /// they are the simulation's record of the bot rather than part of it
+    pub fn restart(&mut self)
+    {
+        *self = Transceiver { stats: self.stats, ..new_transceiver() };
+    }
+
+    /// Set every count in the transceiver's stats back to 0, e.g. at the start of a new experiment
+    pub fn reset_stats(&mut self)
+    {
+        self.stats = CommStats::default();
+    }
+
+    /// Count a message received intact
+    pub(crate) fn record_received(&mut self)
+    {
+        self.stats.received = self.stats.received.saturating_add(1);
+    }
+
+    /// Count a message that was received corrupted and dropped
+    pub(crate) fn record_crc_failure(&mut self)
+    {
+        self.stats.crc_failures = self.stats.crc_failures.saturating_add(1);
+    }
+
+    /// Returns whether a message has been received since the transceiver was created
+    pub fn has_received_message(&self) -> bool
+    {
+        self.message_received == 1
+    }
+
+    /// Advance the transmission clock by the ticks the bot's own clock has counted since the last poll,
+    /// and pick a message if it is time to transmit. The oldest message in the outbox goes first. If the
+    /// outbox is empty the message_tx callback is asked instead, and same as kilolib, it keeps being
+    /// asked every tick until it has a message. Nothing is asked for while the transceiver is backing
+    /// off after a failed transmission, or if the bot's clock hasn't counted a tick. The channel is
+    /// shared in simulation ticks, so a bot whose clock counted several still transmits at most once
+    /// # Arguments
+    /// * 'enabled' - Whether the bot is allowed to transmit. The clock keeps running either way
+    /// * 'ticks' - Ticks the bot's clock has counted since the last poll
+    /// # Returns
+    /// * The message to transmit, or None if it isn't time to transmit or there is nothing to send
+    pub fn poll_tx(&mut self, enabled: bool, ticks: u32) -> Option<Message>
+    {
+        self.tx_clock = self.tx_clock.saturating_add(ticks);
+        if self.backoff > 0
+        {
+            self.backoff = self.backoff.saturating_sub(ticks);
+            return None;
+        }
+        if ticks == 0 || !enabled || self.tx_clock < self.tx_period
+        {
+            return None;
+        }
+        self.sending_from_outbox = !self.outbox.is_empty();
+        match self.outbox.front()
+        {
+            Some(msg) => Some(msg.clone()),
+            None => (self.message_tx)(),
+        }
+    }
+
+    /// Report that the message from poll_tx was transmitted without detecting any contention.
+    /// Restarts the transmission clock, takes the message out of the outbox if it came from there,
+    /// and runs the message_tx_success callback
+    pub fn transmitted(&mut self)
+    {
+        if self.sending_from_outbox
+        {
+            self.outbox.pop_front();
+            self.sending_from_outbox = false;
+        }
+        self.tx_clock = 0;
+        self.failed_attempts = 0;
+        self.stats.attempted = self.stats.attempted.saturating_add(1);
+        self.stats.transmitted = self.stats.transmitted.saturating_add(1);
+        (self.message_tx_success)();
+    }
+
+    /// Report that the message from poll_tx couldn't be transmitted, either because the channel was
+    /// busy or because another transmission collided with it. The transceiver waits a random number
+    /// of ticks before trying again, from a window that doubles with every failure in a row.
+    /// A message from the outbox stays at the front of it, to be tried again
+    /// # Arguments
+    /// * 'collided' - true if the message was sent but collided, false if the channel was busy
+    /// * 'rng' - Source of randomness for the back-off
+    pub fn transmit_failed(&mut self, collided: bool, rng: &mut Random)
+    {
+        self.sending_from_outbox = false;
+        self.stats.attempted = self.stats.attempted.saturating_add(1);
+        if collided
+        {
+            self.stats.collided = self.stats.collided.saturating_add(1);
+        } else {
+            self.stats.channel_busy = self.stats.channel_busy.saturating_add(1);
+        }
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        let window = 1u64 << self.failed_attempts.min(MAX_BACKOFF_EXPONENT);
+        self.backoff = 1 + (rng.next_u64() % window) as u32;
+    }
+
+    /// Returns whether the transceiver is waiting to try again after a failed transmission
+    pub fn is_backing_off(&self) -> bool
+    {
+        self.backoff > 0
+    }
+
+    /// Hand a received message to the message_rx callback, then run the message_rx_success callback
+    /// # Arguments
+    /// * 'msg' - The received message
+    /// * 'dist' - Signal strength the message was received with
+    pub fn receive(&mut self, msg: Message, dist: DistanceMeasurement)
+    {
+        self.message_received = 1;
+        (self.message_rx)(msg, dist);
+        (self.message_rx_success)();
+    }
+}
+
+/// Default message_tx callback, which never has a message to send
+fn no_message_tx() -> Option<Message>
+{
+    None
+}
+
+/// Default message_rx callback, which ignores the message
+fn no_message_rx(_msg: Message, _dist: DistanceMeasurement) {}
+
+/// Default callback for events that the program isn't interested in
+fn no_callback() {}
+
+//Create a new transceiver with callbacks that do nothing
+pub fn new_transceiver() -> Transceiver
+{
+    Transceiver {
+        message_received: 0,
+        message_tx: Box::new(no_message_tx),
+        message_rx: Box::new(no_message_rx),
+        message_tx_success: Box::new(no_callback),
+        message_rx_success: Box::new(no_callback),
+        outbox: VecDeque::new(),
+        outbox_capacity: DEFAULT_OUTBOX_CAPACITY,
+        sending_from_outbox: false,
+        dropped: 0,
+        unreported_drops: 0,
+        tx_period: DEFAULT_TX_PERIOD,
+        tx_clock: 0,
+        backoff: 0,
+        failed_attempts: 0,
+        stats: CommStats::default(),
+    }
+}
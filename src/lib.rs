@@ -0,0 +1,5 @@
+pub mod hal;
+pub mod board_controller;
+pub mod kilobot;
+pub mod board;
+pub mod simulation;
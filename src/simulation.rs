@@ -0,0 +1,439 @@
+/*
+ * simulation
+ * Purpose: Advance the board through time and turn the Kilobots' motor values into movement
+ *
+ * Time is discrete, and advances in ticks at the same rate as kilolib's kilo_ticks clock (32 Hz).
+ * Each bot counts its own kilo_ticks, which only match the simulation's ticks if its clock has no
+ * phase offset or drift
+ *
+ */
+
+use crate::board::Board;
+use crate::board::board_map::BoardMap;
+use crate::board::pose::Pose;
+use crate::board_controller::BoardController;
+use crate::kilobot::state::KiloState;
+use crate::simulation::random::{Random, DEFAULT_SEED};
+use crate::simulation::collision::{CollisionEvent, CollisionResponse, CollisionTarget};
+use crate::simulation::messaging::DropEvent;
+
+pub mod kinematics;
+pub mod collision;
+pub mod messaging;
+pub mod random;
+
+/// Number of ticks in one second of simulated time. Matches kilolib's kilo_ticks
+pub const TICKS_PER_SECOND: u32 = 32;
+/// Number of board spaces around a bot that are checked for other bots it could collide with.
+/// Covers a full body diameter plus however far the bots could have moved this tick
+const COLLISION_RANGE: usize = 3;
+
+/// Discrete-time simulation of a board of Kilobots
+/// # Fields
+/// * 'board_controller' - Controller that owns and manipulates the simulated board
+/// * 'ticks' - Number of ticks that have passed since the simulation started
+/// * 'collision_response' - What bots do when they collide with something
+/// * 'collisions' - Collisions that happened during the last tick
+/// * 'drops' - Messages dropped from full outboxes during the last tick
+/// * 'corruption_rate' - Chance of each message between bots being corrupted on its way to a receiver
+/// * 'seed' - Seed every random number in the simulation comes from
+/// * 'rng' - Source of randomness for the simulation itself, e.g. for message loss
+/// * 'next_stream' - Stream of random numbers given to the next bot, counted in the order bots are seeded
+pub struct Simulation
+{
+    board_controller: BoardController,
+    ticks: u32,
+    collision_response: CollisionResponse,
+    collisions: Vec<CollisionEvent>,
+    drops: Vec<DropEvent>,
+    corruption_rate: f64,
+    seed: u64,
+    rng: Random,
+    next_stream: u64,
+}
+
+impl Simulation
+{
+    /// Create a new simulation that takes ownership of a board, seeded with DEFAULT_SEED
+    /// # Arguments
+    /// * 'board' - Board to simulate
+    pub fn new(board: Board) -> Simulation
+    {
+        Simulation::with_seed(board, DEFAULT_SEED)
+    }
+
+    /// Create a new simulation that takes ownership of a board. Two simulations with the same seed,
+    /// board and programs play out exactly the same way
+    /// # Arguments
+    /// * 'board' - Board to simulate
+    /// * 'seed' - Seed for every random number in the simulation
+    pub fn with_seed(board: Board, seed: u64) -> Simulation
+    {
+        let mut sim = Simulation { board_controller: BoardController::new(board), ticks: 0,
+            collision_response: CollisionResponse::Slide, collisions: Vec::new(), drops: Vec::new(), corruption_rate: 0.0, seed, rng: Random::new(seed),
+            next_stream: 0 };
+        sim.set_seed(seed);
+        sim
+    }
+
+    /// Restart every random number stream in the simulation from a new seed. The simulation gets a
+    /// stream of its own, and each bot gets an independent one picked by its uid and the order it was
+    /// seeded in: bots on the board now in order of index, then bots added later in the order they
+    /// first run. Both orders only depend on how the board was set up, so runs still repeat, and bots
+    /// that share a uid don't share their random numbers
+    /// # Arguments
+    /// * 'seed' - New seed
+    pub fn set_seed(&mut self, seed: u64)
+    {
+        self.seed = seed;
+        self.rng = Random::new(seed);
+        self.next_stream = 0;
+        for index in self.board().bot_map.get_occupied_indices()
+        {
+            if let Ok(loc) = self.board_controller.board.bot_map.get_mut_bot_location_at_index(index)
+            {
+                loc.bot_mut().seed_rng(seed, self.next_stream);
+                self.next_stream += 1;
+            }
+        }
+    }
+
+    /// Give the clock of every bot on the board a random drift and start phase, since real bots don't
+    /// share a clock. Each bot draws from its own stream of random numbers, which rand_hard doesn't use,
+    /// so runs still repeat. Bots added to the board afterwards keep a clock that matches the simulation's
+    /// # Arguments
+    /// * 'drift' - Standard deviation of the fraction each clock runs fast or slow by, e.g. 0.01
+    /// * 'max_phase' - Most ticks a clock can start ahead of the simulation. Phases are drawn uniformly from 0 to max_phase
+    pub fn randomize_clocks(&mut self, drift: f64, max_phase: f64)
+    {
+        let seed = self.seed;
+        for index in self.board().bot_map.get_occupied_indices()
+        {
+            if let Ok(loc) = self.board_controller.board.bot_map.get_mut_bot_location_at_index(index)
+            {
+                let bot = loc.bot_mut();
+                if !bot.has_seeded_rng()
+                {
+                    bot.seed_rng(seed, self.next_stream);
+                    self.next_stream += 1;
+                }
+                let rng = bot.env_rng_mut();
+                let (bot_drift, phase) = (rng.next_gaussian() * drift, rng.next_f64() * max_phase.max(0.0));
+                let clock = bot.get_clock_mut();
+                clock.set_drift(bot_drift);
+                clock.set_phase(phase);
+            }
+        }
+    }
+
+    /// Get the seed every random number in the simulation comes from
+    pub fn get_seed(&self) -> u64
+    {
+        self.seed
+    }
+
+    /// Set what bots do when they collide with another bot or the edge of the board. Defaults to Slide
+    /// # Arguments
+    /// * 'response' - New collision response
+    pub fn set_collision_response(&mut self, response: CollisionResponse)
+    {
+        self.collision_response = response;
+    }
+
+    /// Get the collisions that happened during the last tick
+    pub fn get_collisions(&self) -> &Vec<CollisionEvent>
+    {
+        &self.collisions
+    }
+
+    /// Set the chance of each message between bots being corrupted on its way to a receiver, e.g. by
+    /// interference. Receivers drop corrupted messages when they check the CRC. Defaults to 0
+    /// # Arguments
+    /// * 'rate' - Probability from 0 (no corruption) to 1 (every message is corrupted)
+    pub fn set_corruption_rate(&mut self, rate: f64)
+    {
+        self.corruption_rate = rate.clamp(0.0, 1.0);
+    }
+
+    /// Get the chance of each message between bots being corrupted on its way to a receiver
+    pub fn get_corruption_rate(&self) -> f64
+    {
+        self.corruption_rate
+    }
+
+    /// Get the messages bots dropped during the last tick because their outbox was full
+    pub fn get_drops(&self) -> &Vec<DropEvent>
+    {
+        &self.drops
+    }
+
+    /// Get an immutable reference to the simulated board
+    pub fn board(&self) -> &Board
+    {
+        &self.board_controller.board
+    }
+
+    /// Get a mutable reference to the simulated board
+    pub fn board_mut(&mut self) -> &mut Board
+    {
+        &mut self.board_controller.board
+    }
+
+    /// Get the number of ticks that have passed since the simulation started
+    pub fn get_ticks(&self) -> u32
+    {
+        self.ticks
+    }
+
+    /// Get the amount of simulated time that has passed, in seconds
+    pub fn get_elapsed_seconds(&self) -> f64
+    {
+        self.ticks as f64 / TICKS_PER_SECOND as f64
+    }
+
+    /// Put every bot on the board into the same firmware state, e.g. IDLE at the start of an experiment
+    /// # Arguments
+    /// * 'state' - State to put the bots in
+    pub fn set_all_bot_states(&mut self, state: KiloState)
+    {
+        for index in self.board().bot_map.get_occupied_indices()
+        {
+            if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+            {
+                loc.bot_mut().set_state(state);
+            }
+        }
+    }
+
+    /// Get the fraction of the bots on the board that still have charge in their battery
+    /// # Returns
+    /// * From 0 (every battery is flat) to 1. 0 if the board is empty
+    pub fn get_availability(&self) -> f64
+    {
+        let indices = self.board().bot_map.get_occupied_indices();
+        if indices.is_empty()
+        {
+            return 0.0;
+        }
+        let available = indices.iter()
+            .filter(|&&i| self.board().get_bot_at_index(i).is_ok_and(|bot| !bot.get_battery().is_depleted()))
+            .count();
+        available as f64 / indices.len() as f64
+    }
+
+    /// Advance the simulation by a single tick. Messages due from the overhead controller are delivered
+    /// first, then every bot runs its program once for each tick its own clock has counted, and moves according to the motor values the program left
+    /// it with, draining its battery as it goes. Bots that changed spaces are moved on the board once everyone has moved, and finally
+    /// any messages the bots are ready to send are delivered
+    pub fn step(&mut self)
+    {
+        let dt = 1.0 / TICKS_PER_SECOND as f64;
+        self.collisions.clear();
+        self.drops.clear();
+        self.deliver_overhead_messages();
+        let ticks = self.ticks;
+        let indices = self.board().bot_map.get_occupied_indices();
+        for &index in &indices
+        {
+            self.update_bot_surroundings(index);
+            if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+            {
+                let bot = loc.bot_mut();
+                for kilo_ticks in bot.get_clock_mut().take_due_ticks()
+                {
+                    bot.run(kilo_ticks, ticks);
+                }
+                bot.get_clock_mut().advance();
+                let count = bot.get_transceiver_mut().take_unreported_drops();
+                if count > 0
+                {
+                    let uid = bot.get_uid();
+                    self.drops.push(DropEvent { uid, count });
+                }
+            }
+        }
+        for index in indices
+        {
+            self.move_bot(index, dt);
+            if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+            {
+                loc.bot_mut().update_battery(dt);
+            }
+        }
+        self.board_mut().bot_map.update_bot_spaces();
+        self.deliver_messages();
+        self.ticks += 1;
+    }
+
+    /// Advance the simulation by a number of ticks
+    /// # Arguments
+    /// * 'ticks' - Number of ticks to advance
+    pub fn run_for_ticks(&mut self, ticks: u32)
+    {
+        for _i in 0..ticks
+        {
+            self.step();
+        }
+    }
+
+    /// Advance the simulation by (at least) the given amount of simulated time
+    /// # Arguments
+    /// * 'seconds' - Number of seconds to advance. Rounded up to a whole number of ticks
+    pub fn run_for_seconds(&mut self, seconds: f64)
+    {
+        self.run_for_ticks((seconds * TICKS_PER_SECOND as f64).ceil() as u32);
+    }
+
+    /// Tell a bot about its surroundings before it runs: whether it is on a charger, and what its
+    /// sensors read. Bots new to the simulation are also given their streams of random numbers
+    /// # Arguments
+    /// * 'index' - Index of the bot on the board
+    fn update_bot_surroundings(&mut self, index: usize)
+    {
+        let seed = self.seed;
+        let pose = match self.board().get_bot_location_at_index(index)
+        {
+            Ok(loc) => *loc.get_pose(),
+            Err(_) => return,
+        };
+        let charger_current = self.board().get_charger_current_at(&pose);
+        let board = &mut self.board_controller.board;
+        if let Ok(loc) = board.bot_map.get_mut_bot_location_at_index(index)
+        {
+            let bot = loc.bot_mut();
+            if !bot.has_seeded_rng()
+            {
+                bot.seed_rng(seed, self.next_stream);
+                self.next_stream += 1;
+            }
+            bot.set_charger_current(charger_current);
+            let light = board.light_map.read(&pose, bot.env_rng_mut());
+            bot.set_ambient_light(light);
+            let temperature = board.temperature_map.read(&pose, bot.env_rng_mut());
+            bot.set_temperature(temperature);
+        }
+    }
+
+    /// Move a single bot according to its motor values, resolving any collisions along the way.
+    /// Only the pose of the bot changes, it stays in the same space on the board until update_bot_spaces
+    /// # Arguments
+    /// * 'index' - Index of the bot on the board
+    /// * 'dt' - Length of the tick in seconds
+    fn move_bot(&mut self, index: usize, dt: f64)
+    {
+        let (uid, current, mut next) = match self.board().get_bot_location_at_index(index)
+        {
+            Ok(loc) => {
+                let bot = loc.bot();
+                let (left, right) = bot.get_spinning_motor_values();
+                let next = kinematics::get_next_pose(loc.get_pose(), left, right, bot.get_ideal_calibration(), bot.get_motor_model(), dt);
+                (bot.get_uid(), *loc.get_pose(), next)
+            },
+            Err(_) => return,
+        };
+        let width = self.board().get_width_mm();
+        let height = self.board().get_height_mm();
+
+        if collision::hits_wall(&current, &next, width, height)
+        {
+            self.collisions.push(CollisionEvent { uid, target: CollisionTarget::Wall });
+            next = match self.collision_response
+            {
+                CollisionResponse::Stop => collision::stop(&current, &next),
+                _ => collision::slide_off_wall(&current, &next, width, height),
+            };
+        }
+
+        for other_index in self.board().bot_map.get_occupied_indices_near(index, COLLISION_RANGE)
+        {
+            let (other_uid, other) = match self.board().get_bot_location_at_index(other_index)
+            {
+                Ok(loc) if other_index != index => (loc.bot().get_uid(), *loc.get_pose()),
+                _ => continue,
+            };
+            if !collision::hits_bot(&current, &next, &other)
+            {
+                continue;
+            }
+            self.collisions.push(CollisionEvent { uid, target: CollisionTarget::Bot(other_uid) });
+            next = match self.collision_response
+            {
+                CollisionResponse::Stop => collision::stop(&current, &next),
+                CollisionResponse::Slide => collision::slide_off_bot(&current, &next, &other),
+                CollisionResponse::Push => {
+                    if self.push_bot(other_index, index, &next)
+                    {
+                        next
+                    } else {
+                        collision::slide_off_bot(&current, &next, &other)
+                    }
+                },
+            };
+        }
+
+        //Resolving one collision can cause another, e.g. sliding off one bot into a second one
+        if self.is_blocked(index, &current, &next)
+        {
+            next = collision::stop(&current, &next);
+        }
+        if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+        {
+            *loc.get_pose_mut() = next;
+        }
+    }
+
+    /// Try to push a bot out of the way of another one
+    /// # Arguments
+    /// * 'index' - Index of the bot being pushed
+    /// * 'pusher_index' - Index of the bot doing the pushing
+    /// * 'pusher' - Pose of the bot doing the pushing, after its move
+    /// # Returns
+    /// * true if the bot was pushed, false if it couldn't be pushed without hitting something else
+    fn push_bot(&mut self, index: usize, pusher_index: usize, pusher: &Pose) -> bool
+    {
+        let current = match self.board().get_bot_location_at_index(index)
+        {
+            Ok(loc) => *loc.get_pose(),
+            Err(_) => return false,
+        };
+        let pushed = collision::get_pushed_pose(pusher, &current);
+        let blocked = self.board().bot_map.get_occupied_indices_near(index, COLLISION_RANGE).iter()
+            .any(|&other_index| other_index != index && other_index != pusher_index
+                && self.hits_bot_at(other_index, &current, &pushed));
+        if blocked || collision::hits_wall(&current, &pushed, self.board().get_width_mm(), self.board().get_height_mm())
+        {
+            return false;
+        }
+        if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+        {
+            *loc.get_pose_mut() = pushed;
+        }
+        true
+    }
+
+    /// Determines whether a move of the bot at an index runs into the edge of the board or any other bot
+    /// # Arguments
+    /// * 'index' - Index of the moving bot
+    /// * 'current' - Pose of the bot before the move
+    /// * 'next' - Pose of the bot after the move
+    fn is_blocked(&self, index: usize, current: &Pose, next: &Pose) -> bool
+    {
+        collision::hits_wall(current, next, self.board().get_width_mm(), self.board().get_height_mm())
+            || self.board().bot_map.get_occupied_indices_near(index, COLLISION_RANGE).iter()
+                .any(|&other_index| other_index != index && self.hits_bot_at(other_index, current, next))
+    }
+
+    /// Determines whether a move runs into the bot at an index
+    /// # Arguments
+    /// * 'index' - Index of the bot that could be hit
+    /// * 'current' - Pose of the moving bot before the move
+    /// * 'next' - Pose of the moving bot after the move
+    fn hits_bot_at(&self, index: usize, current: &Pose, next: &Pose) -> bool
+    {
+        match self.board().get_bot_location_at_index(index)
+        {
+            Ok(loc) => collision::hits_bot(current, next, loc.get_pose()),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+/*
+ * collision
+ * Purpose: Detect and resolve collisions between round kilobots, and between kilobots and the
+ * edges of the board
+ *
+ * A move only counts as a collision if it pushes a bot further into something it is touching.
+ * That way bots that are placed overlapping each other (or the edge of the board) can still
+ * move apart, they just can't move any closer together
+ *
+ */
+
+use crate::board::pose::Pose;
+use crate::kilobot::BODY_DIAMETER;
+
+/// Slack in mm when comparing distances, so that bots resting exactly against each other after a
+/// slide or push don't register as colliding again due to rounding
+const TOLERANCE: f64 = 1e-9;
+
+/// What a bot does when it collides with something
+pub enum CollisionResponse
+{
+    /// The bot doesn't move, but can still turn on the spot
+    Stop,
+    /// The bot slides along whatever it hit, keeping the part of its movement that isn't
+    /// directly into the obstacle
+    Slide,
+    /// The bot pushes the other bot out of its way. If the other bot can't be pushed, or the bot
+    /// hit the edge of the board, it slides instead
+    Push,
+}
+
+/// What a bot collided with
+pub enum CollisionTarget
+{
+    /// Another bot, identified by its uid
+    Bot(u16),
+    /// The edge of the board
+    Wall,
+}
+
+/// A collision that happened during a tick
+/// # Fields
+/// * 'uid' - UID of the bot that moved into something
+/// * 'target' - What the bot collided with
+pub struct CollisionEvent
+{
+    pub uid: u16,
+    pub target: CollisionTarget,
+}
+
+/// Determines whether moving a bot pushes it further into another bot
+/// # Arguments
+/// * 'current' - Pose of the moving bot before the move
+/// * 'next' - Pose of the moving bot after the move
+/// * 'other' - Pose of the other bot
+/// # Returns
+/// * true if the bots overlap after the move, and are closer together than before it
+pub fn hits_bot(current: &Pose, next: &Pose, other: &Pose) -> bool
+{
+    let next_dist = next.distance_to(other);
+    next_dist < BODY_DIAMETER - TOLERANCE && next_dist < current.distance_to(other) - TOLERANCE
+}
+
+/// Determines whether moving a bot pushes it further past any edge of the board
+/// # Arguments
+/// * 'current' - Pose of the moving bot before the move
+/// * 'next' - Pose of the moving bot after the move
+/// * 'width' - Width of the board in mm
+/// * 'height' - Height of the board in mm
+/// # Returns
+/// * true if the bot's body crosses an edge after the move, and is further across it than before
+pub fn hits_wall(current: &Pose, next: &Pose, width: f64, height: f64) -> bool
+{
+    let radius = BODY_DIAMETER / 2.0;
+    (next.x < radius - TOLERANCE && next.x < current.x - TOLERANCE)
+        || (next.x > width - radius + TOLERANCE && next.x > current.x + TOLERANCE)
+        || (next.y < radius - TOLERANCE && next.y < current.y - TOLERANCE)
+        || (next.y > height - radius + TOLERANCE && next.y > current.y + TOLERANCE)
+}
+
+/// Gets the pose of a bot that stopped instead of moving. It keeps any turn from the move
+/// # Arguments
+/// * 'current' - Pose of the bot before the move
+/// * 'next' - Pose the bot was moving to
+pub fn stop(current: &Pose, next: &Pose) -> Pose
+{
+    Pose::new(current.x, current.y, next.get_heading())
+}
+
+/// Gets the pose of a bot that slid along the edges of the board instead of moving past them
+/// # Arguments
+/// * 'current' - Pose of the bot before the move
+/// * 'next' - Pose the bot was moving to
+/// * 'width' - Width of the board in mm
+/// * 'height' - Height of the board in mm
+pub fn slide_off_wall(current: &Pose, next: &Pose, width: f64, height: f64) -> Pose
+{
+    let radius = BODY_DIAMETER / 2.0;
+    let mut x = next.x;
+    let mut y = next.y;
+    if x < radius && x < current.x
+    {
+        x = current.x.min(radius);
+    } else if x > width - radius && x > current.x {
+        x = current.x.max(width - radius);
+    }
+    if y < radius && y < current.y
+    {
+        y = current.y.min(radius);
+    } else if y > height - radius && y > current.y {
+        y = current.y.max(height - radius);
+    }
+    Pose::new(x, y, next.get_heading())
+}
+
+/// Gets the pose of a bot that slid around another bot instead of moving into it. The bot is moved
+/// back out along the line between the two centers until it is no deeper into the other bot than it
+/// was before the move
+/// # Arguments
+/// * 'current' - Pose of the bot before the move
+/// * 'next' - Pose the bot was moving to
+/// * 'other' - Pose of the bot that was hit
+pub fn slide_off_bot(current: &Pose, next: &Pose, other: &Pose) -> Pose
+{
+    let next_dist = next.distance_to(other);
+    if next_dist <= TOLERANCE
+    {
+        return stop(current, next);
+    }
+    let dist = current.distance_to(other).min(BODY_DIAMETER);
+    let scale = dist / next_dist;
+    Pose::new(other.x + (next.x - other.x) * scale, other.y + (next.y - other.y) * scale, next.get_heading())
+}
+
+/// Gets the pose a bot ends up in after being pushed by another bot, so that the two are just touching
+/// # Arguments
+/// * 'pusher' - Pose of the bot doing the pushing, after its move
+/// * 'pushed' - Pose of the bot being pushed, before it is pushed
+pub fn get_pushed_pose(pusher: &Pose, pushed: &Pose) -> Pose
+{
+    let dist = pusher.distance_to(pushed);
+    if dist <= TOLERANCE
+    {
+        return *pushed;
+    }
+    let scale = BODY_DIAMETER / dist;
+    Pose::new(pusher.x + (pushed.x - pusher.x) * scale, pusher.y + (pushed.y - pusher.y) * scale, pushed.get_heading())
+}
@@ -0,0 +1,77 @@
+/*
+ * kinematics
+ * Purpose: Turn the duty cycles of a kilobot's motors into movement
+ *
+ * The kilobot is modelled as a differential drive robot, using kilolib's convention for which way
+ * each motor turns it: the left motor pivots the bot to the left and the right motor pivots it to
+ * the right. The bot travels forward at the average speed of its two motors, and turns at a rate set
+ * by the difference between them, so spinning only the left motor curves the bot to the left
+ *
+ * How fast each side moves depends on how the motor value compares to the bot's ideal calibration.
+ * Running a motor at its ideal value moves that side at full speed, so a bot whose stored calibration
+ * is off from its ideal one drives at the wrong speed, and curves when it should go straight.
+ * Motor values are compared through the bot's MotorModel, since speed isn't linear in duty cycle
+ *
+ */
+
+use crate::board::pose::Pose;
+use crate::kilobot::{MOVE_SPEED, ROTATION_SPEED};
+use crate::kilobot::calibration::Calibration;
+use crate::kilobot::motor::MotorModel;
+
+/// Gets the velocity produced by a pair of motor values
+/// # Arguments
+/// * 'left' - Duty cycle of the left motor
+/// * 'right' - Duty cycle of the right motor
+/// * 'ideal' - Motor values that move this bot exactly straight, or turn it exactly on the spot
+/// * 'model' - How the bot's motors respond to their duty cycle
+/// # Returns
+/// * (f64, f64) - (forward speed in mm/sec, turn rate in degrees/sec clockwise)
+pub fn get_velocity_from_motors(left: u8, right: u8, ideal: &Calibration, model: &MotorModel) -> (f64, f64)
+{
+    //A motor spinning on its own is turning the bot, both together are driving it straight
+    let (left_ideal, right_ideal) = if left > 0 && right > 0
+    {
+        (ideal.straight_left, ideal.straight_right)
+    } else {
+        (ideal.turn_left, ideal.turn_right)
+    };
+    let left = get_relative_speed(left, left_ideal, model);
+    let right = get_relative_speed(right, right_ideal, model);
+    let speed = MOVE_SPEED * (left + right) / 2.0;
+    let turn_rate = ROTATION_SPEED as f64 * (right - left);
+    (speed, turn_rate)
+}
+
+/// Gets where a bot ends up after running its motors for a length of time
+/// # Arguments
+/// * 'pose' - Starting position and heading of the bot
+/// * 'left' - Duty cycle of the left motor
+/// * 'right' - Duty cycle of the right motor
+/// * 'ideal' - Motor values that move this bot exactly straight, or turn it exactly on the spot
+/// * 'model' - How the bot's motors respond to their duty cycle
+/// * 'dt' - Length of time the motors are running, in seconds
+/// # Returns
+/// * New pose of the bot
+pub fn get_next_pose(pose: &Pose, left: u8, right: u8, ideal: &Calibration, model: &MotorModel, dt: f64) -> Pose
+{
+    let (speed, turn_rate) = get_velocity_from_motors(left, right, ideal, model);
+    let mut next = *pose;
+    next.advance(speed * dt, turn_rate * dt);
+    next
+}
+
+/// Gets the speed of one side of the bot, compared to its speed with the motor at its ideal value
+/// # Arguments
+/// * 'duty' - Duty cycle of the motor, or 0 if it has stalled
+/// * 'ideal' - Ideal value of the motor for the movement being made
+/// * 'model' - How the motor responds to its duty cycle
+fn get_relative_speed(duty: u8, ideal: u8, model: &MotorModel) -> f64
+{
+    let ideal_speed = model.response(ideal);
+    if ideal_speed <= 0.0
+    {
+        return 0.0;
+    }
+    model.response(duty) / ideal_speed
+}
@@ -0,0 +1,290 @@
+/*
+ * messaging
+ * Purpose: Deliver messages between the kilobots on the board
+ *
+ * Every tick, each bot's transceiver is polled. Bots with a message to send become signal sources
+ * on a SignalMap used just for that tick. The SignalMap only narrows down which senders might be in
+ * range, since bots can stand anywhere in their space, so a bot only receives a message if its center
+ * is within COMM_RANGE of the sender's. It gets it along with the signal strength its distance sensor measured
+ *
+ * Messages from the overhead controller skip the SignalMap, since it reaches the whole board
+ *
+ */
+
+use crate::board::{CoordinatePair, CELL_SIZE};
+use crate::board::board_map::BoardMap;
+use crate::board::signal_map::{SignalMap, SignalSource};
+use crate::kilobot::messages::Message;
+use crate::kilobot::distance::DistanceMeasurement;
+use crate::kilobot::battery::TX_CURRENT;
+use crate::simulation::{Simulation, TICKS_PER_SECOND};
+
+/// Range of the IR transceiver in mm, between the centers of the sending and receiving bots
+pub const COMM_RANGE: f64 = 100.0;
+/// Time it takes to transmit a message, in ms. A message is 12 bytes, sent with a start and stop bit
+/// around each byte at roughly 30 kbit/s
+pub const MESSAGE_DURATION_MS: f64 = 4.0;
+/// Time it takes for a transmission to be detected by carrier sensing, in ms. Two bots in range of each
+/// other that start transmitting closer together than this can't hear each other in time, and collide
+pub const CARRIER_SENSE_MS: f64 = 0.5;
+
+/// Messages a bot dropped during a tick because its outbox was full
+/// # Fields
+/// * 'uid' - uid of the bot
+/// * 'count' - Number of messages dropped
+pub struct DropEvent
+{
+    pub uid: u16,
+    pub count: u32,
+}
+
+/// A message that a bot wants to transmit this tick
+/// # Fields
+/// * 'index' - Index of the sending bot on the board
+/// * 'coord' - Coordinates of the sending bot, which is also the location of its signal source
+/// * 'msg' - The message being sent
+/// * 'start' - Time within the tick that the bot starts transmitting, in ms
+/// * 'outcome' - What happened to the transmission
+struct Transmission
+{
+    index: usize,
+    coord: CoordinatePair,
+    msg: Message,
+    start: f64,
+    outcome: TxOutcome,
+}
+
+impl Transmission
+{
+    /// Determines whether this transmission is on the air at the same time as another one
+    fn overlaps(&self, other: &Transmission) -> bool
+    {
+        (self.start - other.start).abs() < MESSAGE_DURATION_MS
+    }
+
+    /// Determines whether the transmission made it onto the air
+    fn was_sent(&self) -> bool
+    {
+        !matches!(self.outcome, TxOutcome::ChannelBusy)
+    }
+}
+
+/// What happened to a transmission
+enum TxOutcome
+{
+    /// Sent without the sender detecting any contention
+    Sent,
+    /// Sent, but the sender heard another bot transmitting at the same time
+    Collided,
+    /// Never sent, since carrier sensing found another bot already transmitting
+    ChannelBusy,
+}
+
+impl Simulation
+{
+    /// Poll every bot's transceiver and deliver any messages to the bots in range of the sender.
+    ///
+    /// Channel access works like kilolib. Each sender starts at a random time within the tick, and first
+    /// checks for another transmission in range (carrier sensing). If the channel is busy it doesn't
+    /// transmit. Bots in range of each other that start too close together both transmit, hear each
+    /// other, and treat the transmission as failed. Either way the sender backs off exponentially, and
+    /// only senders that detected no contention run their message_tx_success callback.
+    ///
+    /// A receiver only gets a message if it heard no other transmission overlapping it, and wasn't
+    /// transmitting itself. Depending on the corruption rate, the message may arrive corrupted. Since there are no acknowledgements, a sender can succeed while some of
+    /// its receivers lose the message to a sender it couldn't hear
+    pub(crate) fn deliver_messages(&mut self)
+    {
+        let indices = self.board().bot_map.get_occupied_indices();
+        let mut transmissions = self.collect_transmissions(&indices);
+        if transmissions.is_empty()
+        {
+            return;
+        }
+
+        //The transmissions get a SignalMap of their own, so the sources on the board's map are left alone
+        let mut signal_map = SignalMap::new(self.board().get_width(), self.board().get_height());
+        for tx in &transmissions
+        {
+            //Every transmitter has a space of its own, so its source always fits. The signal reaches far
+            //enough to cover any bot within COMM_RANGE, wherever the two bots are in their spaces
+            let radius = (COMM_RANGE + CELL_SIZE * std::f64::consts::SQRT_2) / CELL_SIZE;
+            if signal_map.add_new_source(SignalSource::new(tx.coord.clone(), radius)).is_some()
+            {
+                unreachable!("two transmitters share a space");
+            }
+        }
+        //Which transmissions each bot is in range of, by position in transmissions
+        let hears: Vec<Vec<usize>> = indices.iter().map(|&index| self.get_transmissions_in_range(&signal_map, index, &transmissions)).collect();
+        let hears_of = |index: usize| -> &Vec<usize> { &hears[indices.iter().position(|&i| i == index).unwrap()] };
+
+        //Carrier sensing, in the order the bots start transmitting
+        for i in 0..transmissions.len()
+        {
+            let busy = hears_of(transmissions[i].index).iter().any(|&j| {
+                let other = &transmissions[j];
+                other.was_sent() && other.start < transmissions[i].start
+                    && transmissions[i].start >= other.start + CARRIER_SENSE_MS && other.overlaps(&transmissions[i])
+            });
+            if busy
+            {
+                transmissions[i].outcome = TxOutcome::ChannelBusy;
+            }
+        }
+        for i in 0..transmissions.len()
+        {
+            let contention = transmissions[i].was_sent() && hears_of(transmissions[i].index).iter()
+                .any(|&j| transmissions[j].was_sent() && transmissions[j].overlaps(&transmissions[i]));
+            if contention
+            {
+                transmissions[i].outcome = TxOutcome::Collided;
+            }
+        }
+
+        for (n, &index) in indices.iter().enumerate()
+        {
+            let own = transmissions.iter().find(|tx| tx.index == index && tx.was_sent());
+            for &i in &hears[n]
+            {
+                let tx = &transmissions[i];
+                if !matches!(tx.outcome, TxOutcome::Sent)
+                {
+                    continue;
+                }
+                let jammed = own.is_some_and(|own| own.overlaps(tx))
+                    || hears[n].iter().any(|&j| j != i && transmissions[j].was_sent() && transmissions[j].overlaps(tx));
+                if jammed
+                {
+                    continue;
+                }
+                let dist = self.get_distance_between(tx.index, index);
+                let mut msg = tx.msg.clone();
+                if self.corruption_rate > 0.0 && self.rng.chance(self.corruption_rate)
+                {
+                    msg.flip_bit(self.rng.next_u64() as usize);
+                }
+                let rng = &mut self.rng;
+                if let Ok(loc) = self.board_controller.board.bot_map.get_mut_bot_location_at_index(index)
+                {
+                    let measurement = loc.bot().get_distance_sensor().measure(dist, rng);
+                    loc.bot_mut().receive_message(msg, measurement);
+                }
+            }
+        }
+
+        for tx in &transmissions
+        {
+            let rng = &mut self.rng;
+            if let Ok(loc) = self.board_controller.board.bot_map.get_mut_bot_location_at_index(tx.index)
+            {
+                if tx.was_sent()
+                {
+                    loc.bot_mut().get_battery_mut().drain(TX_CURRENT, MESSAGE_DURATION_MS / 1000.0);
+                }
+                let transceiver = loc.bot_mut().get_transceiver_mut();
+                match tx.outcome
+                {
+                    TxOutcome::Sent => transceiver.transmitted(),
+                    TxOutcome::Collided => transceiver.transmit_failed(true, rng),
+                    TxOutcome::ChannelBusy => transceiver.transmit_failed(false, rng),
+                }
+            }
+        }
+    }
+
+    /// Deliver every message the overhead controller is due to send this tick to the bots in its
+    /// region. Each bot can miss a message, depending on the controller's loss rate
+    pub(crate) fn deliver_overhead_messages(&mut self)
+    {
+        let ticks = self.ticks;
+        let broadcasts = self.board_mut().overhead_controller.take_due(ticks);
+        if broadcasts.is_empty()
+        {
+            return;
+        }
+        let loss_rate = self.board().overhead_controller.get_loss_rate();
+        let indices = self.board().bot_map.get_occupied_indices();
+        for broadcast in &broadcasts
+        {
+            for &index in &indices
+            {
+                let in_region = match self.board().get_bot_location_at_index(index)
+                {
+                    Ok(loc) => broadcast.region.contains(loc.get_pose()),
+                    Err(_) => false,
+                };
+                if !in_region || self.rng.chance(loss_rate)
+                {
+                    continue;
+                }
+                if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+                {
+                    //The controller is overhead, so there is no meaningful distance to it
+                    loc.bot_mut().receive_message(broadcast.msg.clone(), DistanceMeasurement::new(0, 0));
+                }
+            }
+        }
+    }
+
+    /// Poll the transceiver of every bot for a message to send
+    /// # Arguments
+    /// * 'indices' - Indices of every bot on the board
+    /// # Returns
+    /// * Every message the bots want to transmit this tick, in the order they start transmitting
+    fn collect_transmissions(&mut self, indices: &[usize]) -> Vec<Transmission>
+    {
+        let mut transmissions = Vec::new();
+        for &index in indices
+        {
+            let coord = self.board().get_coord_from_index(&index).ok().unwrap();
+            if let Ok(loc) = self.board_mut().bot_map.get_mut_bot_location_at_index(index)
+            {
+                if let Some(msg) = loc.bot_mut().poll_tx()
+                {
+                    let start = self.rng.next_f64() * 1000.0 / TICKS_PER_SECOND as f64;
+                    transmissions.push(Transmission { index, coord, msg, start, outcome: TxOutcome::Sent });
+                }
+            }
+        }
+        transmissions.sort_by(|a, b| a.start.total_cmp(&b.start));
+        transmissions
+    }
+
+    /// Find the transmissions whose signal reaches a bot, other than its own. The signal map picks out
+    /// the senders that might be in range, and only those within COMM_RANGE of the bot are kept
+    /// # Arguments
+    /// * 'signal_map' - SignalMap with a signal source for every transmission this tick
+    /// * 'index' - Index of the bot
+    /// * 'transmissions' - Every transmission this tick
+    /// # Returns
+    /// * Positions in transmissions of the ones the bot is in range of
+    fn get_transmissions_in_range(&self, signal_map: &SignalMap, index: usize, transmissions: &[Transmission]) -> Vec<usize>
+    {
+        let coord = self.board().get_coord_from_index(&index).ok().unwrap();
+        let sources = match signal_map.get_signals_at_coord(&coord)
+        {
+            Ok(signal) => &signal.sources,
+            Err(_) => return Vec::new(),
+        };
+        transmissions.iter().enumerate()
+            .filter(|(_, tx)| tx.index != index && sources.contains(&tx.coord.as_u8_tuple())
+                && self.get_distance_between(tx.index, index) <= COMM_RANGE)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Get the distance between the centers of two bots
+    /// # Arguments
+    /// * 'a' - Index of the first bot
+    /// * 'b' - Index of the second bot
+    /// # Returns
+    /// * Distance in mm, or infinity if either index doesn't have a bot
+    fn get_distance_between(&self, a: usize, b: usize) -> f64
+    {
+        match (self.board().get_bot_location_at_index(a), self.board().get_bot_location_at_index(b))
+        {
+            (Ok(a), Ok(b)) => a.get_pose().distance_to(b.get_pose()),
+            _ => f64::INFINITY,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * random
+ * Purpose: Small deterministic random number generator for the simulation
+ *
+ * Anything random in the simulation (message loss, noise) draws from one of these, so that a run
+ * started with the same seed always plays out the same way. The simulation has a stream of its own,
+ * and every bot gets an independent stream derived from the same seed and its uid
+ *
+ */
+
+/// Seed used when none is given
+pub const DEFAULT_SEED: u64 = 0x5EED;
+
+/// Pseudo-random number generator (xorshift64*). Not suitable for anything but simulation
+/// # Fields
+/// * 'state' - Current state of the generator. Never zero
+pub struct Random
+{
+    state: u64,
+}
+
+impl Random
+{
+    /// Create a new generator
+    /// # Arguments
+    /// * 'seed' - Seed for the generator. Any value is fine, including zero
+    pub fn new(seed: u64) -> Random
+    {
+        //Scramble the seed (splitmix64) so that similar seeds give unrelated streams
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Random { state: if z == 0 { DEFAULT_SEED } else { z } }
+    }
+
+    /// Create one of several independent generators that share a seed
+    /// # Arguments
+    /// * 'seed' - Seed shared by all the streams
+    /// * 'stream' - Which stream to create, e.g. the uid of a bot
+    pub fn new_stream(seed: u64, stream: u64) -> Random
+    {
+        Random::new(Random::new(seed).next_u64() ^ stream)
+    }
+
+    /// Get the next 64 random bits
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Get a random number in [0, 1)
+    pub fn next_f64(&mut self) -> f64
+    {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Get a random number from the standard normal distribution (mean 0, standard deviation 1)
+    pub fn next_gaussian(&mut self) -> f64
+    {
+        //Box-Muller transform. 1 - u keeps the logarithm away from zero
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+
+    /// Randomly decide whether something with the given probability happens
+    /// # Arguments
+    /// * 'probability' - Chance of returning true, from 0 (never) to 1 (always)
+    pub fn chance(&mut self, probability: f64) -> bool
+    {
+        self.next_f64() < probability
+    }
+}